@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use serde::Deserialize;
+
+use autobox_effect_parser::registry::EffectRegistry;
+
+use crate::baseline::{Baseline, BaselineChange};
+use crate::callgraph::CallGraph;
+use crate::config::Config;
+use crate::effects::{self, Definitions, EffectWarning};
+use crate::paths::{self, PathContext};
+use crate::policy::{self, PolicyEngine, PolicyOptions};
+use crate::sarif;
+use crate::taint::{self, TaintFinding, TaintSource};
+use crate::gaps::{self, Gap};
+use crate::{analyze_entrypoint, env_vars_read, find_entrypoint, read_ast, read_declarations, SideEffect};
+
+/// The registry of custom effects read from the crate root, or the working directory when a single
+/// file is analyzed
+pub const DEFAULT_REGISTRY: &str = "autobox.effects";
+
+/// How `analyze` prints a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// Each side effect, with where the unknown parts of its arguments come from
+    #[default]
+    Text,
+    /// A SARIF 2.1.0 log of the side effects, declaration warnings, taint findings and gaps
+    Sarif,
+}
+
+/// Analyzes a crate, ie: `Analyzer::new("./example-app").entrypoint("main").run()`
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    crate_root: PathBuf,
+    source_file: Option<PathBuf>,
+    entrypoints: Vec<String>,
+    declarations: Vec<PathBuf>,
+    ignored: Vec<String>,
+    assumed_env: HashMap<String, String>,
+    working_dir: Option<String>,
+    registry: Option<EffectRegistry>,
+    effects_file: Option<PathBuf>,
+}
+
+impl Analyzer {
+    /// Analyze the crate at `crate_root`, starting from `src/main.rs` or `src/lib.rs`. A path to a
+    /// source file analyzes just that file
+    pub fn new(crate_root: impl Into<PathBuf>) -> Self {
+        Self {
+            crate_root: crate_root.into(),
+            source_file: None,
+            entrypoints: Vec::new(),
+            declarations: Vec::new(),
+            ignored: Vec::new(),
+            assumed_env: HashMap::new(),
+            working_dir: None,
+            registry: None,
+            effects_file: None,
+        }
+    }
+
+    /// Analyze `path` rather than `src/main.rs` or `src/lib.rs`, ie: a binary in `src/bin`, still
+    /// looking for the registry in the crate
+    pub fn source_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.source_file = Some(path.into());
+        self
+    }
+
+    /// Start from the function named `name` rather than the one with `#[effect::entrypoint]`. Given
+    /// more than once, the side effects of every entrypoint are reported together
+    pub fn entrypoint(mut self, name: impl Into<String>) -> Self {
+        self.entrypoints.push(name.into());
+        self
+    }
+
+    /// Add the `#[effect::declare]` stubs and `effect::define!` definitions in the Rust file at
+    /// `path` to the crate
+    pub fn declarations(mut self, path: impl Into<PathBuf>) -> Self {
+        self.declarations.push(path.into());
+        self
+    }
+
+    /// Don't follow calls to the function named `name` as it's named in call paths, ie:
+    /// `debug_dump` or `Metrics::flush`, as if it had no side effects
+    pub fn ignore(mut self, name: impl Into<String>) -> Self {
+        self.ignored.push(name.into());
+        self
+    }
+
+    /// Use the options in `config`, which later calls override
+    pub fn config(mut self, config: &Config) -> Self {
+        for name in config.entrypoints.iter() {
+            self = self.entrypoint(name);
+        }
+        for path in config.declarations.iter() {
+            self = self.declarations(config.resolve(path));
+        }
+        for name in config.ignore.iter() {
+            self = self.ignore(name);
+        }
+        for (name, value) in config.assume_env.iter() {
+            self = self.assume_env(name, value);
+        }
+        if let Some(working_dir) = &config.working_dir {
+            self = self.working_dir(working_dir);
+        }
+        if let Some(effects) = &config.effects {
+            self = self.effects_file(config.resolve(effects));
+        }
+        self
+    }
+
+    /// Assume an environment variable has `value` rather than being unknown. An assumed `HOME`
+    /// is what `~` expands to
+    pub fn assume_env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.assumed_env.insert(name.into(), value.into());
+        self
+    }
+
+    /// The directory the program runs in, which relative paths are resolved against
+    pub fn working_dir(mut self, working_dir: impl Into<String>) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+
+    /// Use `registry` rather than the builtin effects and those in `autobox.effects`
+    pub fn registry(mut self, registry: EffectRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Add the effects in the registry at `path` to the builtin ones, rather than `autobox.effects`
+    pub fn effects_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.effects_file = Some(path.into());
+        self
+    }
+
+    /// The source file analyzed, and where the default registry is looked for
+    fn source(&self) -> Result<(PathBuf, PathBuf)> {
+        if let Some(source) = &self.source_file {
+            return Ok((source.clone(), self.crate_root.join(DEFAULT_REGISTRY)));
+        }
+        if !self.crate_root.is_dir() {
+            return Ok((self.crate_root.clone(), PathBuf::from(DEFAULT_REGISTRY)));
+        }
+        let source = ["src/main.rs", "src/lib.rs"].iter()
+            .map(|source| self.crate_root.join(source))
+            .find(|source| source.exists())
+            .ok_or_else(|| eyre!("{}: no src/main.rs or src/lib.rs", self.crate_root.display()))?;
+        Ok((source, self.crate_root.join(DEFAULT_REGISTRY)))
+    }
+
+    /// Every side effect reachable from the entrypoint, with their paths normalized so they can
+    /// be used in a policy
+    pub fn run(&self) -> Result<AnalysisReport> {
+        let (source, default_registry) = self.source()?;
+        let mut registry = match (&self.registry, &self.effects_file) {
+            (Some(registry), _) => registry.clone(),
+            (None, Some(path)) => effects::load_registry(Some(path))?,
+            (None, None) if default_registry.exists() => effects::load_registry(Some(&default_registry))?,
+            (None, None) => effects::load_registry(None)?,
+        };
+
+        let mut ast = read_ast(&source).map_err(|error| eyre!("{}: {}", source.display(), error))?;
+        for path in self.declarations.iter() {
+            let declarations = read_declarations(path).map_err(|error| eyre!("{}: {}", path.display(), error))?;
+            ast.items.extend(declarations.items);
+        }
+        let definitions = Definitions::collect_from_ast(&ast);
+        definitions.register(&mut registry);
+        let warnings = effects::check_declarations(&ast, &registry).into_iter()
+            .chain(definitions.check(&registry))
+            .collect();
+
+        let entrypoints = match self.entrypoints.is_empty() {
+            true => vec![find_entrypoint(&ast, None)
+                .ok_or_else(|| eyre!("{}: no function with #[effect::entrypoint]", source.display()))?],
+            false => self.entrypoints.iter()
+                .map(|name| find_entrypoint(&ast, Some(name))
+                    .ok_or_else(|| eyre!("{}: no function named `{}`", source.display(), name)))
+                .collect::<Result<_>>()?,
+        };
+        let mut side_effects = Vec::new();
+        let mut gaps: Vec<Gap> = Vec::new();
+        let mut call_graph = CallGraph::default();
+        for entrypoint in entrypoints {
+            let (found, found_gaps, calls) = analyze_entrypoint(&ast, entrypoint, &self.assumed_env, &self.ignored);
+            side_effects.extend(found);
+            for gap in found_gaps {
+                if !gaps.iter().any(|known| known.same_as(&gap)) {
+                    gaps.push(gap);
+                }
+            }
+            call_graph.merge(calls);
+        }
+        let mut expanded = definitions.expand(&side_effects);
+        let path_context = PathContext {
+            home: self.assumed_env.get("HOME").cloned(),
+            working_dir: self.working_dir.clone(),
+        };
+        paths::normalize_side_effects(&mut side_effects, &path_context, &registry);
+        paths::normalize_side_effects(&mut expanded, &path_context, &registry);
+        call_graph.annotate(&side_effects);
+
+        Ok(AnalysisReport {
+            source,
+            declarations: self.declarations.clone(),
+            side_effects,
+            expanded,
+            warnings,
+            gaps,
+            call_graph,
+            registry,
+        })
+    }
+}
+
+/// What the analysis found, and what can be derived from it
+#[derive(Debug, Clone)]
+pub struct AnalysisReport {
+    /// The file that was analyzed
+    pub source: PathBuf,
+    /// The declarations files whose items were added to the analyzed file
+    pub declarations: Vec<PathBuf>,
+    /// Every side effect reachable from the entrypoint, as declared
+    pub side_effects: Vec<SideEffect>,
+    /// Effects the registry doesn't know or that have the wrong number of arguments, which won't be
+    /// in any policy
+    pub warnings: Vec<EffectWarning>,
+    /// What the analysis couldn't model, which may hide side effects, ie: unsupported syntax or
+    /// calls to functions from other crates that aren't declared
+    pub gaps: Vec<Gap>,
+    /// The calls followed from the entrypoint, with the side effects reached through each
+    pub call_graph: CallGraph,
+    expanded: Vec<SideEffect>,
+    registry: EffectRegistry,
+}
+
+impl AnalysisReport {
+    /// Every side effect with those made with `effect::define!` expanded into what they're made of
+    pub fn expanded(&self) -> &[SideEffect] {
+        &self.expanded
+    }
+
+    /// The file a location, gap or warning in `file` is in, ie: `Gap::file`
+    pub fn file<'a>(&'a self, file: &'a Option<PathBuf>) -> &'a Path {
+        file.as_deref().unwrap_or(&self.source)
+    }
+
+    /// Whether everything reachable from the entrypoint was modeled, so that no side effects can
+    /// be missing
+    pub fn is_sound(&self) -> bool {
+        self.gaps.is_empty()
+    }
+
+    /// The gaps grouped by category, ie: `unsafe blocks`, in the order they were first found
+    pub fn gaps_by_category(&self) -> Vec<(&'static str, Vec<&Gap>)> {
+        gaps::by_category(&self.gaps)
+    }
+
+    /// The registry the side effects were checked against, including the crate's definitions
+    pub fn registry(&self) -> &EffectRegistry {
+        &self.registry
+    }
+
+    /// Every environment variable the entrypoint can read, `*` for those whose name isn't known
+    pub fn env_vars_read(&self) -> Vec<String> {
+        env_vars_read(&self.side_effects)
+    }
+
+    /// A baseline of the side effects, to commit and check later changes against
+    pub fn baseline(&self) -> Baseline {
+        Baseline::new(&self.side_effects)
+    }
+
+    /// Side effects that `baseline` doesn't cover, and the effects of `baseline` that are gone
+    pub fn check(&self, baseline: &Baseline) -> Vec<BaselineChange> {
+        baseline.check(&self.side_effects)
+    }
+
+    /// A policy for `engine` allowing the expanded side effects
+    pub fn policy(&self, engine: PolicyEngine, options: PolicyOptions) -> String {
+        policy::generate(engine, options, &self.registry, &self.expanded)
+    }
+
+    /// The side effects, warnings, gaps and arguments of the default sinks tainted by any source, as
+    /// a SARIF 2.1.0 log for code scanning
+    pub fn sarif(&self) -> String {
+        sarif::log(self)
+    }
+
+    /// Arguments of expanded side effects named in `sinks` that hold values from `sources`
+    pub fn tainted(&self, sources: &[TaintSource], sinks: &[String]) -> Vec<TaintFinding<'_>> {
+        taint::find_tainted(&self.expanded, sources, sinks)
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use eyre::{bail, eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::SideEffect;
+
+/// The baseline read from the crate root, or the working directory when a single file is analyzed
+pub const DEFAULT_BASELINE: &str = "autobox.lock";
+
+/// The version of the baseline format written, so that older baselines can be told apart
+const BASELINE_VERSION: u32 = 1;
+
+/// The side effects a program is known to have, committed so that new or broader ones are caught
+/// in review
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    version: u32,
+    pub side_effects: Vec<BaselineEffect>,
+}
+
+/// A side effect as it's recorded in a baseline, with each argument as the globs of its alternatives
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineEffect {
+    pub name: String,
+    pub arguments: Vec<Vec<String>>,
+    pub call_path: Vec<String>,
+}
+
+impl BaselineEffect {
+    pub fn of(side_effect: &SideEffect) -> Self {
+        Self {
+            name: side_effect.name.clone(),
+            arguments: side_effect.arguments.iter().map(|argument| argument.globs()).collect(),
+            call_path: side_effect.call_path.clone(),
+        }
+    }
+
+    /// Whether `other` is the same effect with arguments this one already allows, through any call
+    /// path. `"~/*.json"` covers `"~/a.json"` but not `"~/*"`
+    pub fn covers(&self, other: &BaselineEffect) -> bool {
+        self.name == other.name
+            && self.arguments.len() == other.arguments.len()
+            && self.arguments.iter().zip(other.arguments.iter()).all(|(ours, theirs)| {
+                theirs.iter().all(|glob| ours.iter().any(|pattern| glob_covers(pattern, glob)))
+            })
+    }
+}
+
+impl Display for BaselineEffect {
+    /// Like a side effect, ie: `reads_file("/etc/app.toml" | "~/*.toml")`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let arguments: Vec<_> = self.arguments.iter()
+            .map(|globs| globs.iter().map(|glob| format!("{:?}", glob)).collect::<Vec<_>>().join(" | "))
+            .collect();
+        write!(f, "{}({})", self.name, arguments.join(", "))
+    }
+}
+
+/// How the side effects found differ from a baseline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaselineChange {
+    /// An effect the baseline has nothing like
+    Added(BaselineEffect),
+    /// An effect the baseline has through the same call path, but with narrower arguments, ie: a
+    /// path that was concrete and is now `*`
+    Broadened {
+        baseline: BaselineEffect,
+        current: BaselineEffect,
+    },
+    /// An effect in the baseline that the program no longer has
+    Removed(BaselineEffect),
+}
+
+impl BaselineChange {
+    /// Whether the change should fail a check. Removed effects only make the baseline too broad
+    pub fn fails(&self) -> bool {
+        !matches!(self, BaselineChange::Removed(_))
+    }
+}
+
+impl Baseline {
+    pub fn new(side_effects: &[SideEffect]) -> Self {
+        let mut effects: Vec<BaselineEffect> = Vec::new();
+        for side_effect in side_effects.iter().map(BaselineEffect::of) {
+            if !effects.contains(&side_effect) {
+                effects.push(side_effect);
+            }
+        }
+        Self {
+            version: BASELINE_VERSION,
+            side_effects: effects,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|error| eyre!("{}: {}", path.display(), error))?;
+        let baseline: Baseline = serde_json::from_str(&contents).map_err(|error| eyre!("{}: {}", path.display(), error))?;
+        if baseline.version != BASELINE_VERSION {
+            bail!("{}: baseline version {} isn't supported, expected {}", path.display(), baseline.version, BASELINE_VERSION);
+        }
+        Ok(baseline)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = format!("{}\n", serde_json::to_string_pretty(self)?);
+        std::fs::write(path, contents).map_err(|error| eyre!("{}: {}", path.display(), error))
+    }
+
+    /// Every effect in `side_effects` that the baseline doesn't cover, then every effect of the
+    /// baseline that was neither broadened nor is still allowed by one of them
+    pub fn check(&self, side_effects: &[SideEffect]) -> Vec<BaselineChange> {
+        let current = Baseline::new(side_effects).side_effects;
+        let mut changes = Vec::new();
+        let mut broadened = Vec::new();
+        for effect in current.iter() {
+            if self.side_effects.iter().any(|known| known.covers(effect)) {
+                continue;
+            }
+            // The effect it widens, or else any with the same call path
+            let same_call: Vec<_> = self.side_effects.iter()
+                .filter(|known| known.name == effect.name && known.call_path == effect.call_path)
+                .collect();
+            let known = same_call.iter().find(|known| effect.covers(known)).or_else(|| same_call.first());
+            changes.push(match known {
+                Some(known) => {
+                    broadened.push(*known);
+                    BaselineChange::Broadened {
+                        baseline: (*known).clone(),
+                        current: effect.clone(),
+                    }
+                }
+                None => BaselineChange::Added(effect.clone()),
+            });
+        }
+        for known in self.side_effects.iter() {
+            let found = current.iter().any(|effect| known.covers(effect) || effect.covers(known));
+            if !found && !broadened.contains(&known) {
+                changes.push(BaselineChange::Removed(known.clone()));
+            }
+        }
+        changes
+    }
+}
+
+/// A piece of a glob as `VariableState::globs` writes them
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GlobToken {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    Star,
+    /// `[a-z]` or `[^/]`, whose special characters are escaped with `\`
+    Class { ranges: Vec<(char, char)>, negated: bool },
+}
+
+impl GlobToken {
+    /// Whether every character this single character token matches is matched by `self`. Classes
+    /// are compared conservatively, so a class is only covered by an equal or wider one
+    fn covers(&self, other: &GlobToken) -> bool {
+        match (self, other) {
+            (_, GlobToken::Star) | (GlobToken::Star, _) => false,
+            (GlobToken::AnyChar, _) => true,
+            (GlobToken::Char(ours), GlobToken::Char(theirs)) => ours == theirs,
+            (GlobToken::Class { ranges, negated }, GlobToken::Char(c)) => {
+                ranges.iter().any(|(start, end)| (*start..=*end).contains(c)) != *negated
+            }
+            (GlobToken::Class { ranges, negated: false }, GlobToken::Class { ranges: theirs, negated: false }) => {
+                theirs.iter().all(|(start, end)| ranges.iter().any(|range| range.0 <= *start && *end <= range.1))
+            }
+            (ours, theirs) => ours == theirs,
+        }
+    }
+}
+
+fn glob_tokens(glob: &str) -> Vec<GlobToken> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => tokens.push(GlobToken::Star),
+            '?' => tokens.push(GlobToken::AnyChar),
+            // An escaped `*`, `?`, `[` or `\` is just that character
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                tokens.push(GlobToken::Char(chars[i]));
+            }
+            '[' => {
+                if let Some((class, end)) = glob_class(&chars, i + 1) {
+                    tokens.push(class);
+                    i = end;
+                } else {
+                    tokens.push(GlobToken::Char('['));
+                }
+            }
+            c => tokens.push(GlobToken::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+// The class whose `[` is just before `i`, and the index of its `]`
+fn glob_class(chars: &[char], mut i: usize) -> Option<(GlobToken, usize)> {
+    let negated = chars.get(i) == Some(&'^');
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    // A character of the class, unescaped
+    let next = |i: &mut usize| -> Option<char> {
+        let c = *chars.get(*i)?;
+        *i += 1;
+        match c {
+            '\\' => {
+                let escaped = *chars.get(*i)?;
+                *i += 1;
+                Some(escaped)
+            }
+            c => Some(c),
+        }
+    };
+    loop {
+        if chars.get(i) == Some(&']') {
+            return Some((GlobToken::Class { ranges, negated }, i));
+        }
+        let start = next(&mut i)?;
+        // `-` is escaped when it's a character of the class, so it always makes a range
+        if chars.get(i) == Some(&'-') {
+            i += 1;
+            ranges.push((start, next(&mut i)?));
+        } else {
+            ranges.push((start, start));
+        }
+    }
+}
+
+// Whether everything `glob` matches is matched by `pattern`
+fn glob_covers(pattern: &str, glob: &str) -> bool {
+    let pattern = glob_tokens(pattern);
+    let glob = glob_tokens(glob);
+    // covered[i][j] is whether pattern[i..] covers glob[j..]. A `*` in the pattern covers any run of
+    // the glob, including its own `*`s
+    let mut covered = vec![vec![false; glob.len() + 1]; pattern.len() + 1];
+    covered[pattern.len()][glob.len()] = true;
+    for i in (0..pattern.len()).rev() {
+        for j in (0..=glob.len()).rev() {
+            covered[i][j] = match pattern[i] {
+                GlobToken::Star => covered[i + 1][j] || (j < glob.len() && covered[i][j + 1]),
+                ref token => j < glob.len() && token.covers(&glob[j]) && covered[i + 1][j + 1],
+            };
+        }
+    }
+    covered[0][0]
+}
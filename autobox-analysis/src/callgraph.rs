@@ -0,0 +1,189 @@
+use std::fmt::Write;
+
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::SideEffect;
+
+/// How a call graph is written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum GraphFormat {
+    /// Graphviz, ie: `autobox callgraph --format dot | dot -Tsvg`
+    Dot,
+    /// Nodes and edges as JSON objects
+    Json,
+}
+
+/// How the analysis knows what a function does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    /// Its effects come from a declaration, ours for std or one written with `#[effect::declare]`
+    Declared,
+    /// Its effects were inferred from its body
+    Inferred,
+    /// A function from another crate or an `extern` block without a declaration
+    External,
+    /// A call we couldn't find the callee of
+    Unresolved,
+}
+
+impl CallKind {
+    fn name(&self) -> &'static str {
+        match self {
+            CallKind::Declared => "declared",
+            CallKind::Inferred => "inferred",
+            CallKind::External => "external",
+            CallKind::Unresolved => "unresolved",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallNode {
+    /// The function as it's named in call paths, ie: `Config::load` or `<closure>`
+    pub name: String,
+    pub kind: CallKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallEdge {
+    pub caller: String,
+    pub callee: String,
+    /// The side effects reached through this call, as declared
+    pub side_effects: Vec<String>,
+}
+
+/// The calls reachable from the entrypoints. A function is a single node however many times it's
+/// called, and so are all closures and all async blocks
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CallGraph {
+    pub entrypoints: Vec<String>,
+    pub nodes: Vec<CallNode>,
+    pub edges: Vec<CallEdge>,
+}
+
+impl CallGraph {
+    pub(crate) fn new(entrypoint: impl Into<String>) -> Self {
+        let entrypoint = entrypoint.into();
+        Self {
+            nodes: vec![CallNode { name: entrypoint.clone(), kind: CallKind::Inferred }],
+            entrypoints: vec![entrypoint],
+            edges: Vec::new(),
+        }
+    }
+
+    /// Add the calls of another entrypoint's graph
+    pub(crate) fn merge(&mut self, other: CallGraph) {
+        for entrypoint in other.entrypoints {
+            if !self.entrypoints.contains(&entrypoint) {
+                self.entrypoints.push(entrypoint);
+            }
+        }
+        for node in other.nodes {
+            if self.node(&node.name).is_none() {
+                self.nodes.push(node);
+            }
+        }
+        for edge in other.edges {
+            if self.edge(&edge.caller, &edge.callee).is_none() {
+                self.edges.push(edge);
+            }
+        }
+    }
+
+    /// Record a call from `caller` to `callee`. Loop bodies are evaluated more than once, so the same
+    /// call can be recorded many times
+    pub(crate) fn add_call(&mut self, caller: &str, callee: &str, kind: CallKind) {
+        if !self.nodes.iter().any(|node| node.name == callee) {
+            self.nodes.push(CallNode { name: callee.to_string(), kind });
+        }
+        if self.edge(caller, callee).is_none() {
+            self.edges.push(CallEdge {
+                caller: caller.to_string(),
+                callee: callee.to_string(),
+                side_effects: Vec::new(),
+            });
+        }
+    }
+
+    /// Annotate every edge with the side effects whose call path goes through it
+    pub(crate) fn annotate(&mut self, side_effects: &[SideEffect]) {
+        for side_effect in side_effects {
+            let described = side_effect.to_string();
+            for call in side_effect.call_path.windows(2) {
+                let index = match self.edge(&call[0], &call[1]) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let edge = &mut self.edges[index];
+                if !edge.side_effects.contains(&described) {
+                    edge.side_effects.push(described.clone());
+                }
+            }
+        }
+    }
+
+    fn edge(&self, caller: &str, callee: &str) -> Option<usize> {
+        self.edges.iter().position(|edge| edge.caller == caller && edge.callee == callee)
+    }
+
+    pub fn node(&self, name: &str) -> Option<&CallNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Json => self.to_json(),
+        }
+    }
+
+    /// The graph for Graphviz. Declared functions are boxes, and what the analysis couldn't see
+    /// into is dashed
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph callgraph {\n");
+        for node in self.nodes.iter() {
+            let attributes = match node.kind {
+                CallKind::Declared => "shape=box",
+                CallKind::Inferred if self.entrypoints.contains(&node.name) => "shape=doubleoctagon",
+                CallKind::Inferred => "shape=ellipse",
+                CallKind::External => "shape=box, style=dashed",
+                CallKind::Unresolved => "shape=ellipse, style=dashed, color=red",
+            };
+            writeln!(dot, "    {} [kind={}, {}];", quote(&node.name), node.kind.name(), attributes).unwrap();
+        }
+        for edge in self.edges.iter() {
+            write!(dot, "    {} -> {}", quote(&edge.caller), quote(&edge.callee)).unwrap();
+            if !edge.side_effects.is_empty() {
+                write!(dot, " [label={}]", quote(&edge.side_effects.join("\n"))).unwrap();
+            }
+            dot.push_str(";\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let graph = json!({
+            "entrypoints": self.entrypoints,
+            "nodes": self.nodes.iter()
+                .map(|node| json!({ "name": node.name, "kind": node.kind.name() }))
+                .collect::<Vec<_>>(),
+            "edges": self.edges.iter()
+                .map(|edge| json!({
+                    "caller": edge.caller,
+                    "callee": edge.callee,
+                    "side_effects": edge.side_effects,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        format!("{:#}\n", graph)
+    }
+}
+
+// A DOT string literal, where newlines become line breaks in labels
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
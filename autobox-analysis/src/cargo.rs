@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use eyre::{bail, eyre, Result};
+use serde::Deserialize;
+
+use crate::analyzer::Analyzer;
+use crate::{find_entrypoint, read_ast};
+
+/// A binary of a workspace package, as `cargo metadata` describes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryTarget {
+    pub package: String,
+    pub name: String,
+    /// The root source file, ie: `src/main.rs` or `src/bin/worker.rs`
+    pub src_path: PathBuf,
+    /// The directory of the package's `Cargo.toml`, where its `autobox.effects` is looked for
+    pub package_dir: PathBuf,
+}
+
+// The parts of `cargo metadata --format-version 1` we use
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    manifest_path: PathBuf,
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+    kind: Vec<String>,
+    src_path: PathBuf,
+}
+
+impl BinaryTarget {
+    /// Whether its root source file has a function with `#[effect::entrypoint]`
+    pub fn has_entrypoint(&self) -> Result<bool> {
+        let ast = read_ast(&self.src_path).map_err(|error| eyre!("{}: {}", self.src_path.display(), error))?;
+        Ok(find_entrypoint(&ast, None).is_some())
+    }
+
+    /// An analyzer of the binary, with the registry of its package
+    pub fn analyzer(&self) -> Analyzer {
+        Analyzer::new(&self.package_dir).source_file(&self.src_path)
+    }
+}
+
+/// Every binary of the workspace at `manifest_path`, or of the one the working directory is in,
+/// from `cargo metadata`. Dependencies aren't resolved, so it works offline
+pub fn workspace_binaries(manifest_path: Option<&Path>) -> Result<Vec<BinaryTarget>> {
+    // Cargo sets `CARGO` to itself when it runs a subcommand
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut command = Command::new(cargo);
+    command.args(["metadata", "--format-version", "1", "--no-deps", "--offline"]);
+    if let Some(manifest_path) = manifest_path {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+    let output = command.output().map_err(|error| eyre!("couldn't run cargo metadata: {}", error))?;
+    if !output.status.success() {
+        bail!("cargo metadata failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    parse_metadata(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The binaries of the workspace members in the output of `cargo metadata`, in the order of the
+/// members
+pub(crate) fn parse_metadata(metadata: &str) -> Result<Vec<BinaryTarget>> {
+    let metadata: Metadata = serde_json::from_str(metadata).map_err(|error| eyre!("cargo metadata: {}", error))?;
+    let mut binaries = Vec::new();
+    for member in metadata.workspace_members.iter() {
+        let package = match metadata.packages.iter().find(|package| package.id == *member) {
+            Some(package) => package,
+            None => continue,
+        };
+        let package_dir = package.manifest_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        for target in package.targets.iter().filter(|target| target.kind.iter().any(|kind| kind == "bin")) {
+            binaries.push(BinaryTarget {
+                package: package.name.clone(),
+                name: target.name.clone(),
+                src_path: target.src_path.clone(),
+                package_dir: package_dir.clone(),
+            });
+        }
+    }
+    Ok(binaries)
+}
@@ -0,0 +1,142 @@
+use std::fmt::{Display, Formatter};
+
+use autobox_effect_parser::ast::{self, PatternAtom};
+
+/// Fixed length classes up to this long are written out in globs, ie: `[0-9][0-9]`, longer ones become `*`
+const MAX_GLOB_REPEAT: usize = 4;
+
+/// Between `min_len` and `max_len` unknown characters, all from `alphabet`, ie: `[0-9]+`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharClass {
+    /// Sorted, non overlapping and non adjacent ranges of characters
+    pub alphabet: Vec<(char, char)>,
+    pub min_len: usize,
+    /// `None` when there is no upper bound
+    pub max_len: Option<usize>,
+}
+
+impl CharClass {
+    pub fn new(alphabet: Vec<(char, char)>, min_len: usize, max_len: Option<usize>) -> Self {
+        Self {
+            alphabet: normalize(alphabet),
+            min_len,
+            max_len,
+        }
+    }
+
+    pub fn from_atom(atom: &PatternAtom) -> Self {
+        let alphabet = match atom.class {
+            ast::CharClass::Literal(c) => vec![(c, c)],
+            ast::CharClass::Any => vec![('\0', char::MAX)],
+            ast::CharClass::Set { ref ranges, negated: false } => ranges.clone(),
+            ast::CharClass::Set { ref ranges, negated: true } => complement(&normalize(ranges.clone())),
+        };
+        Self::new(alphabet, atom.min, atom.max)
+    }
+
+    /// The class as a glob, ie: `[0-9]` for a single digit. Classes without a fixed length can't be
+    /// written as a glob and become `*`
+    pub fn glob(&self) -> String {
+        match self.max_len {
+            Some(max_len) if max_len == self.min_len && max_len <= MAX_GLOB_REPEAT => {
+                self.bracket().repeat(max_len)
+            }
+            _ => "*".to_string(),
+        }
+    }
+
+    // `[a-z_]`, or `[^/]` when that's shorter
+    fn bracket(&self) -> String {
+        let excluded = complement(&self.alphabet);
+        if excluded.is_empty() {
+            return "?".to_string();
+        }
+        let (negated, ranges) = if excluded.len() < self.alphabet.len() {
+            ("^", &excluded)
+        } else {
+            ("", &self.alphabet)
+        };
+        let mut bracket = format!("[{}", negated);
+        for (start, end) in ranges.iter() {
+            push_escaped(&mut bracket, *start);
+            if char_after(*start) == Some(*end) {
+                push_escaped(&mut bracket, *end);
+            } else if start != end {
+                bracket.push('-');
+                push_escaped(&mut bracket, *end);
+            }
+        }
+        bracket.push(']');
+        bracket
+    }
+}
+
+impl Display for CharClass {
+    /// As a regex, ie: `[0-9]+`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.bracket())?;
+        match (self.min_len, self.max_len) {
+            (1, Some(1)) => Ok(()),
+            (0, None) => write!(f, "*"),
+            (1, None) => write!(f, "+"),
+            (0, Some(1)) => write!(f, "?"),
+            (min_len, None) => write!(f, "{{{},}}", min_len),
+            (min_len, Some(max_len)) if min_len == max_len => write!(f, "{{{}}}", min_len),
+            (min_len, Some(max_len)) => write!(f, "{{{},{}}}", min_len, max_len),
+        }
+    }
+}
+
+fn push_escaped(bracket: &mut String, c: char) {
+    if [']', '[', '\\', '^', '-'].contains(&c) {
+        bracket.push('\\');
+    }
+    bracket.push(c);
+}
+
+// Sort and merge ranges
+fn normalize(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort();
+    let mut normalized: Vec<(char, char)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match normalized.last_mut() {
+            Some((_, last_end)) if char_after(*last_end).map(|next| start <= next).unwrap_or(true) => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => normalized.push((start, end)),
+        }
+    }
+    normalized
+}
+
+// Every character that isn't in `ranges`, which must be normalized
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut complement = Vec::new();
+    let mut next = Some('\0');
+    for (start, end) in ranges.iter() {
+        if let (Some(from), Some(to)) = (next, char_before(*start)) {
+            if from <= to {
+                complement.push((from, to));
+            }
+        }
+        next = char_after(*end);
+    }
+    if let Some(from) = next {
+        complement.push((from, char::MAX));
+    }
+    complement
+}
+
+fn char_after(c: char) -> Option<char> {
+    match c as u32 {
+        0xD7FF => Some('\u{E000}'),
+        n => char::from_u32(n.checked_add(1)?),
+    }
+}
+
+fn char_before(c: char) -> Option<char> {
+    match c as u32 {
+        0xE000 => Some('\u{D7FF}'),
+        n => char::from_u32(n.checked_sub(1)?),
+    }
+}
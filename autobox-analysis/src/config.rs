@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use eyre::{eyre, Result};
+use serde::Deserialize;
+
+use crate::analyzer::ReportFormat;
+use crate::policy::{ExecTransition, PolicyEngine};
+
+/// The configuration read from the crate or workspace root
+pub const DEFAULT_CONFIG: &str = "autobox.toml";
+
+/// What `init` writes, with every option commented out
+pub const CONFIG_TEMPLATE: &str = r#"# Configuration for autobox. Command line flags override what's set here, and
+# relative paths are relative to this file.
+
+# Functions to start the analysis from, rather than the one with
+# `#[effect::entrypoint]`
+# entrypoints = ["main"]
+
+# Rust files with `#[effect::declare]` stubs and `effect::define!` definitions
+# to add to the crate, ie: for functions generated by macros
+# declarations = ["autobox/declarations.rs"]
+
+# The registry of custom effects, `autobox.effects` next to the crate when not set
+# effects = "autobox.effects"
+
+# The directory the program runs in, which relative paths are resolved against
+# working_dir = "/srv/app"
+
+# Functions whose calls aren't followed, as they're named in call paths
+# ignore = ["debug_dump", "Metrics::flush"]
+
+# How `analyze` prints what it found: "text" or "sarif"
+# format = "text"
+
+# Values environment variables are assumed to have rather than being unknown
+[assume_env]
+# HOME = "/home/svc"
+
+[generate]
+# The policy `generate` writes when `--policy-engine` isn't given: "apparmor",
+# "seccomp", "landlock" or "nftables"
+# policy_engine = "apparmor"
+
+[generate.apparmor]
+# How the programs the entrypoint runs are confined: "ix" or "px"
+# exec_transition = "ix"
+
+[generate.seccomp]
+# Allow the `execve` starting the program, for filters installed before it
+# exec_once = false
+"#;
+
+/// Options for a crate, usually from its `autobox.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub entrypoints: Vec<String>,
+    pub declarations: Vec<PathBuf>,
+    pub effects: Option<PathBuf>,
+    pub working_dir: Option<String>,
+    pub ignore: Vec<String>,
+    pub format: Option<ReportFormat>,
+    pub assume_env: BTreeMap<String, String>,
+    pub generate: GenerateConfig,
+    /// The directory the configuration was read from, which its paths are relative to
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerateConfig {
+    pub policy_engine: Option<PolicyEngine>,
+    pub apparmor: ApparmorConfig,
+    pub seccomp: SeccompConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApparmorConfig {
+    pub exec_transition: Option<ExecTransition>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SeccompConfig {
+    pub exec_once: Option<bool>,
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|error| eyre!("{}", error))
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|error| eyre!("{}: {}", path.display(), error))?;
+        let mut config = Config::parse(&contents).map_err(|error| eyre!("{}: {}", path.display(), error))?;
+        config.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(config)
+    }
+
+    /// The `autobox.toml` in `dir` or the closest directory above it, so that a workspace can have
+    /// one for all of its crates
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        let dir = dir.canonicalize().ok()?;
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(DEFAULT_CONFIG))
+            .find(|config| config.is_file())
+    }
+
+    /// `path` relative to the configuration
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use autobox_effect_parser::ast::EffectDefinition;
+use autobox_effect_parser::registry::EffectRegistry;
+use eyre::{eyre, Result};
+use syn::visit::{self, Visit};
+
+use crate::{evaluate_expr, parse_declaration, Derivation, Location, SideEffect};
+
+/// The builtin effects, extended by those in the registry at `path` when there is one
+pub fn load_registry(path: Option<&Path>) -> Result<EffectRegistry> {
+    let mut registry = EffectRegistry::builtin();
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(registry),
+    };
+    let custom = std::fs::read_to_string(path)?;
+    registry.extend(EffectRegistry::parse(&custom).map_err(|error| eyre!("{}: {}", path.display(), error))?);
+    Ok(registry)
+}
+
+/// An effect in a `declare` macro that the registry doesn't know, or that is given the wrong
+/// number of arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectWarning {
+    /// The function, method or definition with the effect
+    pub fn_name: String,
+    /// The declarations file the function or definition is in, or `None` for the analyzed file
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub message: String,
+}
+
+impl Display for EffectWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: in `{}`: {}", self.line, self.column, self.fn_name, self.message)
+    }
+}
+
+/// Check the effects of every function and method with a `declare` macro against the registry
+pub fn check_declarations(ast: &syn::File, registry: &EffectRegistry) -> Vec<EffectWarning> {
+    let mut checker = DeclarationChecker {
+        registry,
+        warnings: Vec::new(),
+    };
+    checker.visit_file(ast);
+    checker.warnings
+}
+
+struct DeclarationChecker<'r> {
+    registry: &'r EffectRegistry,
+    warnings: Vec<EffectWarning>,
+}
+
+impl DeclarationChecker<'_> {
+    fn check(&mut self, attrs: &[syn::Attribute], sig: &syn::Signature) {
+        let declaration = match parse_declaration(attrs) {
+            Some(declaration) => declaration,
+            None => return,
+        };
+        let location = Location::of(&sig.ident);
+        for stmt in declaration.side_effects.iter().flat_map(|side_effects| side_effects.side_effect_stmts.iter()) {
+            if let Some(message) = self.registry.check(stmt.side_effect_name, stmt.side_effect_arguments.len()) {
+                self.warnings.push(EffectWarning {
+                    fn_name: sig.ident.to_string(),
+                    file: location.file.clone(),
+                    line: location.line,
+                    column: location.column,
+                    end_line: location.end_line,
+                    end_column: location.end_column,
+                    message,
+                });
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for DeclarationChecker<'_> {
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        self.check(&node.attrs, &node.sig);
+        visit::visit_item_fn(self, node);
+    }
+
+    fn visit_impl_item_method(&mut self, node: &'ast syn::ImplItemMethod) {
+        self.check(&node.attrs, &node.sig);
+        visit::visit_impl_item_method(self, node);
+    }
+
+    fn visit_trait_item_method(&mut self, node: &'ast syn::TraitItemMethod) {
+        self.check(&node.attrs, &node.sig);
+        visit::visit_trait_item_method(self, node);
+    }
+}
+
+/// Effects defined with `effect::define!`, which expand into other effects
+#[derive(Debug, Clone, Default)]
+pub struct Definitions {
+    /// Each definition with the location of its macro
+    definitions: Vec<(EffectDefinition<'static>, Location)>,
+}
+
+impl Definitions {
+    pub fn collect_from_ast(ast: &syn::File) -> Self {
+        let mut definitions = Definitions::default();
+        definitions.visit_file(ast);
+        definitions
+    }
+
+    /// Add every definition to the registry, so that declarations can use them
+    pub fn register(&self, registry: &mut EffectRegistry) {
+        for (definition, _) in self.definitions.iter() {
+            registry.add(definition.effect_kind());
+        }
+    }
+
+    /// Warnings about definitions that use variables they don't have, or effects that aren't in
+    /// the registry or are given the wrong number of arguments
+    pub fn check(&self, registry: &EffectRegistry) -> Vec<EffectWarning> {
+        let mut warnings = Vec::new();
+        for (definition, location) in self.definitions.iter() {
+            let mut warn = |message| warnings.push(EffectWarning {
+                fn_name: definition.name.to_string(),
+                file: location.file.clone(),
+                line: location.line,
+                column: location.column,
+                end_line: location.end_line,
+                end_column: location.end_column,
+                message,
+            });
+            for variable in definition.unknown_variables() {
+                warn(format!("`{}` is not a parameter of `{}`", variable, definition.name));
+            }
+            for stmt in definition.expansion.iter() {
+                if let Some(message) = registry.check(stmt.side_effect_name, stmt.side_effect_arguments.len()) {
+                    warn(message);
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Every side effect with defined effects replaced by what they expand into. The expanded effects
+    /// keep the name of the one they came from at the end of their call path, ie:
+    /// `reads_file("/etc/app.toml")` in `main -> load -> reads_config`
+    pub fn expand(&self, side_effects: &[SideEffect]) -> Vec<SideEffect> {
+        let mut expanded = Vec::new();
+        for side_effect in side_effects {
+            self.expand_into(side_effect.clone(), &mut expanded, &mut Vec::new());
+        }
+        expanded
+    }
+
+    // `expanding` holds the definitions being expanded, so that recursive ones stop
+    fn expand_into(&self, side_effect: SideEffect, expanded: &mut Vec<SideEffect>, expanding: &mut Vec<String>) {
+        let definition = self.definitions.iter()
+            .find(|(definition, _)| definition.name == side_effect.name)
+            .filter(|(definition, _)| definition.params.len() == side_effect.arguments.len())
+            .filter(|(definition, _)| definition.unknown_variables().is_empty())
+            .filter(|(definition, _)| !expanding.iter().any(|name| name == definition.name));
+        let (definition, location) = match definition {
+            Some(definition) => definition,
+            None => return expanded.push(side_effect),
+        };
+
+        let arguments: HashMap<_, _> = definition.params.iter()
+            .map(|param| param.name)
+            .zip(side_effect.arguments.iter())
+            .collect();
+        let mut call_path = side_effect.call_path.clone();
+        call_path.push(side_effect.name.clone());
+        // The defined effect is "called" where it's defined
+        let mut call_sites = side_effect.call_sites.clone();
+        call_sites.push(location.clone());
+        expanding.push(side_effect.name.clone());
+        for stmt in definition.expansion.iter() {
+            let primitive = SideEffect {
+                name: stmt.side_effect_name.to_string(),
+                arguments: stmt.side_effect_arguments.iter().map(|arg| evaluate_expr(arg, &arguments)).collect(),
+                repeated: side_effect.repeated,
+                imprecise: side_effect.imprecise,
+                call_path: call_path.clone(),
+                call_sites: call_sites.clone(),
+                derivations: stmt.side_effect_arguments.iter().map(|arg| Some(Derivation::of(arg, &arguments))).collect(),
+            };
+            self.expand_into(primitive, expanded, expanding);
+        }
+        expanding.pop();
+    }
+}
+
+impl<'ast> Visit<'ast> for Definitions {
+    fn visit_item_macro(&mut self, node: &'ast syn::ItemMacro) {
+        let is_define = node.mac.path.segments.last().map(|segment| segment.ident == "define").unwrap_or(false);
+        if is_define {
+            // Leaked like declarations are, so definitions can borrow from it
+            let tokens = Box::leak(node.mac.tokens.to_string().into_boxed_str());
+            if let Ok(("", definition)) = EffectDefinition::parse(tokens) {
+                self.definitions.push((definition, Location::of(&node.mac)));
+            }
+        }
+        visit::visit_item_macro(self, node);
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// Something reachable from the entrypoint that the analysis can't model. The side effects it
+/// hides are missing, so a policy generated despite a gap may be too narrow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap {
+    pub kind: GapKind,
+    /// The declarations file the gap is in, or `None` for the analyzed file
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    /// The calls made from the entrypoint to reach the gap, ending with the function it's in
+    pub call_path: Vec<String>,
+}
+
+impl Gap {
+    /// Whether `other` is the same gap, reached through any call path
+    pub(crate) fn same_as(&self, other: &Gap) -> bool {
+        self.kind == other.kind && self.file == other.file && self.line == other.line && self.column == other.column
+    }
+}
+
+impl Display for Gap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)?;
+        if !self.call_path.is_empty() {
+            write!(f, " in {}", self.call_path.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GapKind {
+    /// Syntax the analysis doesn't support, which is treated as an unknown value, ie: `x as u16`
+    Unsupported(String),
+    /// A call to a function we can't find in the crate, ie: one brought in with `use`
+    UnresolvedCallee(String),
+    /// A function from another crate that isn't declared, ie: `std::fs::remove_file`
+    UndeclaredExternal(String),
+    /// An `unsafe` block, which can do anything through raw pointers. Its body is still analyzed
+    Unsafe,
+    /// A call to a function from an `extern` block
+    Ffi(String),
+    /// `asm!`, which can make any syscall
+    InlineAsm,
+    /// A call to a function that's already being evaluated, which isn't followed again. Its side
+    /// effects with the arguments of the recursive call are missing
+    Recursion(String),
+    /// A module in another file, ie: `mod handlers;`, which isn't loaded. Calls into it aren't followed
+    UnloadedModule(String),
+}
+
+impl GapKind {
+    /// What gaps of this kind are called in a summary
+    pub fn category(&self) -> &'static str {
+        match self {
+            GapKind::Unsupported(_) => "unsupported constructs",
+            GapKind::UnresolvedCallee(_) => "unresolved callees",
+            GapKind::UndeclaredExternal(_) => "external functions without a declaration",
+            GapKind::Unsafe => "unsafe blocks",
+            GapKind::Ffi(_) => "FFI calls",
+            GapKind::InlineAsm => "inline assembly",
+            GapKind::Recursion(_) => "recursive calls",
+            GapKind::UnloadedModule(_) => "modules in other files",
+        }
+    }
+}
+
+impl Display for GapKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GapKind::Unsupported(syntax) => write!(f, "unsupported {}", syntax),
+            GapKind::UnresolvedCallee(name) => write!(f, "unresolved call to `{}`", name),
+            GapKind::UndeclaredExternal(name) => write!(f, "call to `{}`, which has no declaration", name),
+            GapKind::Unsafe => write!(f, "unsafe block"),
+            GapKind::Ffi(name) => write!(f, "FFI call to `{}`", name),
+            GapKind::InlineAsm => write!(f, "inline assembly"),
+            GapKind::Recursion(name) => write!(f, "recursive call to `{}`", name),
+            GapKind::UnloadedModule(name) => write!(f, "module `{}` in another file, which isn't analyzed", name),
+        }
+    }
+}
+
+/// Every category of gap with the gaps in it, in the order the categories were first found
+pub fn by_category(gaps: &[Gap]) -> Vec<(&'static str, Vec<&Gap>)> {
+    let mut categories: Vec<(&'static str, Vec<&Gap>)> = Vec::new();
+    for gap in gaps {
+        let category = gap.kind.category();
+        match categories.iter_mut().find(|(name, _)| *name == category) {
+            Some((_, gaps)) => gaps.push(gap),
+            None => categories.push((category, vec![gap])),
+        }
+    }
+    categories
+}
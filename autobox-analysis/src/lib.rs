@@ -0,0 +1,4447 @@
+//! Infers the side effects of a Rust program, starting at its entrypoint, so that a sandbox
+//! allowing only those effects can be generated.
+//!
+//! ```no_run
+//! use autobox_analysis::{Analyzer, PolicyEngine, PolicyOptions};
+//!
+//! let report = Analyzer::new("./example-app").entrypoint("main").run()?;
+//! for side_effect in report.side_effects.iter() {
+//!     println!("{}", side_effect);
+//! }
+//! print!("{}", report.policy(PolicyEngine::Apparmor, PolicyOptions::default()));
+//! # Ok::<(), eyre::Report>(())
+//! ```
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use eyre::Result;
+use syn::{Ident, ItemFn, Stmt};
+use syn::visit::{self, Visit};
+
+use autobox_effect_parser::ast::{Arg, ArithmeticOp, CharClass as PatternClass, DeclareMacro, Expr, Pattern};
+
+use methods::{Methods, ResolvedMethod};
+use process::ProcessCommand;
+
+pub use autobox_effect_parser::registry::EffectRegistry;
+pub use analyzer::{AnalysisReport, Analyzer, ReportFormat, DEFAULT_REGISTRY};
+pub use baseline::{Baseline, BaselineChange, BaselineEffect, DEFAULT_BASELINE};
+pub use callgraph::{CallEdge, CallGraph, CallKind, CallNode, GraphFormat};
+pub use cargo::{workspace_binaries, BinaryTarget};
+pub use char_class::CharClass;
+pub use config::{ApparmorConfig, Config, GenerateConfig, SeccompConfig, CONFIG_TEMPLATE, DEFAULT_CONFIG};
+pub use effects::EffectWarning;
+pub use gaps::{Gap, GapKind};
+pub use policy::{ExecTransition, PolicyEngine, PolicyOptions};
+pub use taint::{Taint, TaintFinding, TaintSource, DEFAULT_SINKS};
+
+mod analyzer;
+mod baseline;
+mod callgraph;
+mod cargo;
+mod char_class;
+mod config;
+mod effects;
+mod gaps;
+mod methods;
+mod network;
+mod paths;
+mod policy;
+mod process;
+mod sarif;
+mod taint;
+
+/// How many times a loop body is evaluated before giving up on its variables and widening them to holes
+const MAX_LOOP_ITERATIONS: usize = 8;
+
+/// Iterator methods whose closure arguments are run once per item
+const ITERATOR_METHODS: &[&str] = &[
+    "iter", "iter_mut", "into_iter", "map", "for_each", "filter", "filter_map", "flat_map",
+    "inspect", "any", "all", "find", "find_map", "position", "fold", "try_for_each",
+    "take_while", "skip_while", "map_while", "collect", "enumerate", "cloned", "copied",
+];
+
+/// Functions that run the closure or future they are given, ie: `tokio::spawn`
+const SPAWN_FNS: &[&str] = &["spawn", "spawn_blocking", "spawn_local"];
+
+/// Methods that read from their receiver into a `&mut` buffer, ie: `stdin.read_line(&mut line)`
+const READ_METHODS: &[&str] = &["read", "read_line", "read_to_string", "read_to_end", "read_exact"];
+
+/// Methods of std types that only derive a value from their receiver, ie: `args.nth(1)` or
+/// `line.trim()`. Any other method on a receiver of unknown type is a gap
+const DERIVING_METHODS: &[&str] = &[
+    "nth", "next", "last", "first", "get", "parse", "trim", "trim_start", "trim_end", "lines",
+    "split", "split_whitespace", "split_once", "strip_prefix", "strip_suffix", "chars", "bytes",
+    "to_lowercase", "to_uppercase", "as_str", "as_ref", "as_bytes", "as_path", "to_str",
+    "to_string_lossy", "display", "unwrap_or", "unwrap_or_default", "unwrap_or_else", "ok",
+    "ok_or", "ok_or_else", "len", "is_empty", "count", "skip", "take", "rev", "peekable",
+    "starts_with", "ends_with", "contains", "is_some", "is_none", "is_ok", "is_err", "lock",
+];
+
+/// Types whose values we do arithmetic on, ie: ports
+const INTEGER_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+fn read_ast(path: &Path) -> Result<syn::File> {
+    let mut file = File::open(path)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+
+    Ok(syn::parse_file(&content)?)
+}
+
+/// Parse the `declare` macro among `attrs`, if there is one
+fn parse_declaration(attrs: &[syn::Attribute]) -> Option<DeclareMacro<'static>> {
+    let attr = attrs.iter().find(|attr| {
+        attr.path.segments.last().map(|segment| segment.ident == "declare").unwrap_or(false)
+    })?;
+    // todo: Yeah yeah I leak it whatever
+    let macro_tokens = Box::leak(attr.tokens.to_string().into_boxed_str());
+    // A declaration that doesn't parse won't compile either, so the function is inferred instead
+    let (_, declaration) = DeclareMacro::parse(macro_tokens).ok()?;
+    Some(declaration)
+}
+
+fn check_if_entrypoint(item_fn: &ItemFn) -> bool {
+    for attr in &item_fn.attrs {
+        if let Some(last_segment) = attr.path.segments.last() {
+            if last_segment.ident == "entrypoint" {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// The function named `name`, or the one with `#[effect::entrypoint]` when no name is given
+fn find_entrypoint<'a>(ast: &'a syn::File, name: Option<&str>) -> Option<&'a ItemFn> {
+    for item in &ast.items {
+        if let syn::Item::Fn(f) = item {
+            let found = match name {
+                Some(name) => f.sig.ident == name,
+                None => check_if_entrypoint(f),
+            };
+            if found {
+                return Some(f);
+            }
+        }
+    }
+    None
+}
+
+#[derive(Clone, Debug, Default)]
+struct ItemFnVisitor<'ast> {
+    item_fns: Vec<&'ast ItemFn>,
+}
+
+impl<'ast> ItemFnVisitor<'ast> {
+    fn collect_from_ast(ast: &'ast syn::File) -> Self {
+        let mut visitor = ItemFnVisitor::default();
+        visitor.visit_file(ast);
+        visitor
+    }
+}
+
+impl<'ast> Visit<'ast> for ItemFnVisitor<'ast> {
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.item_fns.push(node);
+        visit::visit_item_fn(self, node);
+    }
+}
+
+/// Functions declared in `extern` blocks, which are implemented outside of Rust
+#[derive(Clone, Debug, Default)]
+struct ForeignFnVisitor<'ast> {
+    foreign_fns: HashSet<&'ast Ident>,
+}
+
+impl<'ast> Visit<'ast> for ForeignFnVisitor<'ast> {
+    fn visit_foreign_item_fn(&mut self, node: &'ast syn::ForeignItemFn) {
+        self.foreign_fns.insert(&node.sig.ident);
+    }
+}
+
+/// Modules whose items are in another file, ie: `mod handlers;`, except for `#[cfg(test)]` ones
+#[derive(Clone, Debug, Default)]
+struct FileModuleVisitor<'ast> {
+    modules: Vec<&'ast syn::ItemMod>,
+}
+
+impl<'ast> Visit<'ast> for FileModuleVisitor<'ast> {
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        let cfg_test = node.attrs.iter().any(|attr| attr.path.is_ident("cfg") && attr.tokens.to_string() == "(test)");
+        if node.content.is_none() && !cfg_test {
+            self.modules.push(node);
+        }
+        visit::visit_item_mod(self, node);
+    }
+}
+
+/// Everything in the crate that can be called
+struct CrateItems<'a> {
+    declared_fns: HashMap<String, DeclaredItemFn<'a>>,
+    item_fns: HashMap<&'a Ident, &'a ItemFn>,
+    methods: Methods<'a>,
+    foreign_fns: HashSet<&'a Ident>,
+    /// Values environment variables are assumed to have, from `--assume-env`
+    assumed_env: &'a HashMap<String, String>,
+    /// Functions whose calls aren't followed, ie: `debug_dump` or `Metrics::flush`
+    ignored: &'a [String],
+    /// What the analysis couldn't model, in the order it was found
+    gaps: RefCell<Vec<Gap>>,
+    /// The functions being evaluated, from the entrypoint down to the current one
+    call_stack: RefCell<Vec<String>>,
+    /// Every call resolved so far, from the function being evaluated to its callee
+    calls: RefCell<CallGraph>,
+}
+
+impl<'a> CrateItems<'a> {
+    fn collect_from_ast(ast: &'a syn::File, assumed_env: &'a HashMap<String, String>, ignored: &'a [String]) -> Self {
+        Self {
+            declared_fns: get_all_declared_fns(ast),
+            item_fns: ItemFnVisitor::collect_from_ast(ast).item_fns.into_iter()
+                .map(|f| (&f.sig.ident, f))
+                .collect(),
+            methods: Methods::collect_from_ast(ast),
+            foreign_fns: {
+                let mut visitor = ForeignFnVisitor::default();
+                visitor.visit_file(ast);
+                visitor.foreign_fns
+            },
+            assumed_env,
+            ignored,
+            gaps: RefCell::new(Vec::new()),
+            call_stack: RefCell::new(Vec::new()),
+            calls: RefCell::new(CallGraph::default()),
+        }
+    }
+
+    /// Record a gap at `node`, in the function being evaluated
+    fn gap(&self, kind: GapKind, node: &impl syn::spanned::Spanned) {
+        let location = Location::of(node);
+        let gap = Gap {
+            kind,
+            file: location.file,
+            line: location.line,
+            column: location.column,
+            end_line: location.end_line,
+            end_column: location.end_column,
+            call_path: self.call_stack.borrow().clone(),
+        };
+        let mut gaps = self.gaps.borrow_mut();
+        if !gaps.iter().any(|known| known.same_as(&gap)) {
+            gaps.push(gap);
+        }
+    }
+
+    /// Record that `syntax` at `node` isn't supported, returning the origin of the hole it becomes
+    fn unsupported(&self, syntax: impl Into<String>, node: &impl syn::spanned::Spanned) -> HoleOrigin {
+        let syntax = syntax.into();
+        self.gap(GapKind::Unsupported(syntax.clone()), node);
+        HoleOrigin::unsupported(syntax, node)
+    }
+
+    /// Whether calls to `callee` are skipped, as if it had no side effects
+    fn is_ignored(&self, callee: &str) -> bool {
+        self.ignored.iter().any(|ignored| ignored == callee)
+    }
+
+    /// Whether `callee` is already being evaluated, so that following the call would never end. The
+    /// call is recorded as a gap at `call_site` when it is
+    fn is_recursive(&self, callee: &str, call_site: &impl syn::spanned::Spanned) -> bool {
+        if !self.call_stack.borrow().iter().any(|caller| caller == callee) {
+            return false;
+        }
+        self.gap(GapKind::Recursion(callee.to_string()), call_site);
+        true
+    }
+
+    /// Record a call to `callee` from the function being evaluated
+    fn call(&self, callee: &str, kind: CallKind) {
+        if let Some(caller) = self.call_stack.borrow().last() {
+            self.calls.borrow_mut().add_call(caller, callee, kind);
+        }
+    }
+
+    /// Evaluate `f` as a call to `callee`, so that gaps found in it have it in their call path
+    fn within<T>(&self, callee: impl Into<String>, f: impl FnOnce() -> T) -> T {
+        self.call_stack.borrow_mut().push(callee.into());
+        let result = f();
+        self.call_stack.borrow_mut().pop();
+        result
+    }
+}
+
+/// Run the analysis starting at `entrypoint`, returning every side effect it can reach, what it
+/// couldn't model and the calls it followed. Environment variables in `assumed_env` have the value
+/// given rather than being holes, and calls to functions in `ignored` aren't followed
+fn analyze_entrypoint(
+    ast: &syn::File,
+    entrypoint: &ItemFn,
+    assumed_env: &HashMap<String, String>,
+    ignored: &[String],
+) -> (Vec<SideEffect>, Vec<Gap>, CallGraph) {
+    let items = CrateItems::collect_from_ast(ast, assumed_env, ignored);
+    items.calls.replace(CallGraph::new(entrypoint.sig.ident.to_string()));
+
+    // Modules in other files aren't loaded, so the side effects of anything in them are missing
+    let mut modules = FileModuleVisitor::default();
+    modules.visit_file(ast);
+    for module in modules.modules {
+        items.gap(GapKind::UnloadedModule(module.ident.to_string()), &module.ident);
+    }
+
+    // The inferred declaration of the entrypoint
+    let fn_arguments = Vec::new(); // no arguments to entrypoint
+    let mut side_effects = Vec::new();
+    items.within(entrypoint.sig.ident.to_string(), || {
+        infer_fn(&mut side_effects, &entrypoint.sig, &entrypoint.block, None, &fn_arguments, &items)
+    });
+    within_call(&mut side_effects, &entrypoint.sig.ident.to_string());
+    for side_effect in side_effects.iter_mut() {
+        side_effect.call_sites.insert(0, Location::of(&entrypoint.sig.ident));
+    }
+    (side_effects, items.gaps.into_inner(), items.calls.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use effects::Definitions;
+    use paths::PathContext;
+
+    use super::*;
+
+    // The side effects of the `#[effect::entrypoint]` of `ast`
+    fn analyze(ast: &syn::File, assumed_env: &HashMap<String, String>) -> Vec<SideEffect> {
+        analyze_entrypoint(ast, find_entrypoint(ast, None).unwrap(), assumed_env, &[]).0
+    }
+
+    #[test]
+    fn trace_var() {
+        let rust_code = r#"
+
+        // declare_ext!(std::path::Path::join, args(a, b), returns(a + b))
+
+        #[effect::declare(
+            args=(a as A, b as B),
+            side_effects=(reads_file(A + '/' + B)),
+            returns=(a + '/' + b),
+        )]
+        fn fn_with_effects(a: &str, b: &str) -> String {
+            // pretend there are effects here
+            std::fs::read_file(format!("{a}/{b}"));
+            format!("{a}/{b}")
+        }
+
+        fn unknown(a: &str, b: &str) -> String {
+            fn_with_effects(a, b)
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let x = "foo";
+            let y = x;
+            let uk = unknown(x, "bar");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let _side_effects = analyze(&ast, &HashMap::new());
+    }
+
+    #[test]
+    fn mutation_through_mut_args() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        #[effect::declare(
+            args=(p as P),
+            mutates=(p = P + ".toml"),
+        )]
+        fn add_extension(p: &mut String) {
+            p.push_str(".toml");
+        }
+
+        fn add_suffix(p: &mut String) {
+            p.push_str("/config");
+            add_extension(p);
+        }
+
+        // Shadowing the parameter doesn't change the caller's value
+        fn read_shadow(p: &mut String) {
+            let p = "/etc/shadow";
+            read(p);
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let mut path = "~".to_string();
+            add_suffix(&mut path);
+            read(&path);
+            let mut data = "/srv/a.json".to_string();
+            read_shadow(&mut data);
+            read(&data);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects: Vec<_> = analyze(&ast, &HashMap::new()).iter().map(ToString::to_string).collect();
+        assert_eq!(side_effects, vec![
+            r#"reads_file("~/config.toml")"#,
+            r#"reads_file("/etc/shadow")"#,
+            r#"reads_file("/srv/a.json")"#,
+        ]);
+    }
+
+    #[test]
+    fn loops_and_iterators() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            for dir in ["/etc/a", "/etc/b"] {
+                read(dir);
+            }
+            ["a.toml", "b.toml"].iter().for_each(|p| read(p));
+
+            let mut path = "~".to_string();
+            loop {
+                path.push_str("/sub");
+                read(&path);
+                break;
+            }
+            read(&path);
+
+            // A `let` in the body shadowing `name` doesn't change it
+            let name = "a.toml";
+            for dir in ["/etc", "/srv"] {
+                let name = dir;
+                read(name);
+            }
+            read(name);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let side_effects: Vec<_> = side_effects.iter()
+            .map(|side_effect| (side_effect.to_string(), side_effect.repeated))
+            .collect();
+        assert_eq!(side_effects, vec![
+            (r#"reads_file("/etc/a" | "/etc/b")"#.to_string(), true),
+            (r#"reads_file("a.toml" | "b.toml")"#.to_string(), true),
+            (r#"reads_file("~*/sub")"#.to_string(), true),
+            (r#"reads_file("~*")"#.to_string(), false),
+            (r#"reads_file("/etc" | "/srv")"#.to_string(), true),
+            (r#"reads_file("a.toml")"#.to_string(), false),
+        ]);
+    }
+
+    #[test]
+    fn branches() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let mut path = "/etc/app.toml".to_string();
+            if std::env::args().count() > 1 {
+                path = "~/.app.toml".to_string();
+                read("/etc/override.toml");
+            }
+            read(&path);
+
+            let log = match std::env::args().nth(2) {
+                Some(dir) => {
+                    let path = dir;
+                    read(&path);
+                    "/var/log/app.log"
+                }
+                None => "/tmp/app.log",
+            };
+            read(log);
+            read(&path);
+
+            let name = if let Some(name) = std::env::args().nth(3) { "custom.toml" } else { "default.toml" };
+            read(name);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects: Vec<_> = analyze(&ast, &HashMap::new()).iter().map(ToString::to_string).collect();
+        assert_eq!(side_effects, vec![
+            r#"reads_file("/etc/override.toml")"#,
+            r#"reads_file("~/.app.toml" | "/etc/app.toml")"#,
+            r#"reads_file("*")"#,
+            r#"reads_file("/var/log/app.log" | "/tmp/app.log")"#,
+            r#"reads_file("~/.app.toml" | "/etc/app.toml")"#,
+            r#"reads_file("custom.toml" | "default.toml")"#,
+        ]);
+    }
+
+    #[test]
+    fn closures_and_fn_items_as_values() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+            returns=(P),
+        )]
+        fn read(p: &str) -> String {
+            std::fs::read_to_string(p).unwrap()
+        }
+
+        #[effect::declare(
+            args=(a as A, b as B),
+            returns=(A + '/' + B),
+        )]
+        fn join(a: &str, b: &str) -> String {
+            format!("{a}/{b}")
+        }
+
+        fn apply(f: impl Fn(&str) -> String, p: &str) -> String {
+            f(p)
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let dir = "/etc";
+            let read_in_dir = |name: &str| read(&join(dir, name));
+            read_in_dir("app.toml");
+            apply(read, "/tmp/x");
+            apply(read_in_dir, "b.toml");
+            let _ = ["/var/a", "/var/b"].iter().map(read);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects: Vec<_> = analyze(&ast, &HashMap::new()).iter().map(ToString::to_string).collect();
+        assert_eq!(side_effects, vec![
+            r#"reads_file("/etc/app.toml")"#,
+            r#"reads_file("/tmp/x")"#,
+            r#"reads_file("/etc/b.toml")"#,
+            r#"reads_file("/var/a" | "/var/b")"#,
+        ]);
+    }
+
+    #[test]
+    fn generic_and_dyn_dispatch() {
+        let rust_code = r#"
+        trait Source {
+            fn load(&self, name: &str) -> String;
+        }
+
+        trait Store {
+            #[effect::declare(
+                args=(self as S, key as K),
+                side_effects=(writes_file("/var/store/" + K)),
+            )]
+            fn put(&self, key: &str);
+        }
+
+        struct FileSource {
+            dir: String,
+        }
+
+        impl FileSource {
+            fn new() -> Self {
+                FileSource { dir: "/etc".to_string() }
+            }
+        }
+
+        impl Source for FileSource {
+            #[effect::declare(
+                args=(name as N),
+                side_effects=(reads_file("/etc/" + N)),
+                returns=("/etc/" + N),
+            )]
+            fn load(&self, name: &str) -> String {
+                std::fs::read_to_string(format!("/etc/{name}")).unwrap()
+            }
+        }
+
+        struct EnvSource;
+
+        impl Source for EnvSource {
+            #[effect::declare(
+                args=(name as N),
+                side_effects=(reads_env(N)),
+            )]
+            fn load(&self, name: &str) -> String {
+                std::env::var(name).unwrap()
+            }
+        }
+
+        struct DiskStore;
+
+        impl Store for DiskStore {
+            fn put(&self, key: &str) {}
+        }
+
+        fn load_config(source: impl Source) -> String {
+            source.load("app.toml")
+        }
+
+        fn load_config_dyn(source: &dyn Source) -> String {
+            source.load("dyn.toml")
+        }
+
+        #[effect::declare(side_effects=(reads_env("SOURCE")))]
+        fn pick_source() -> Box<dyn Source> {
+            Box::new(EnvSource)
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            load_config(FileSource::new());
+            load_config(EnvSource);
+            let source = pick_source();
+            load_config_dyn(&source);
+            DiskStore.put("cache");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects: Vec<_> = analyze(&ast, &HashMap::new()).iter()
+            .map(|side_effect| (side_effect.to_string(), side_effect.imprecise))
+            .collect();
+        assert_eq!(side_effects, vec![
+            (r#"reads_file("/etc/app.toml")"#.to_string(), false),
+            (r#"reads_env("app.toml")"#.to_string(), false),
+            (r#"reads_env("SOURCE")"#.to_string(), false),
+            (r#"reads_env("dyn.toml")"#.to_string(), true),
+            (r#"reads_file("/etc/dyn.toml")"#.to_string(), true),
+            (r#"writes_file("/var/store/cache")"#.to_string(), false),
+        ]);
+    }
+
+    #[test]
+    fn async_fns_and_blocks() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+            returns=(P),
+        )]
+        async fn read(p: &str) -> String {
+            tokio::fs::read_to_string(p).await.unwrap()
+        }
+
+        async fn load(dir: &str) -> String {
+            read(dir).await
+        }
+
+        #[effect::entrypoint]
+        #[tokio::main]
+        async fn main() {
+            let config = load("/etc/app.toml").await;
+            let dir = "/var/cache";
+            let refresh = async move {
+                read(dir).await;
+            };
+            let unused = async {
+                read("/never").await;
+            };
+            tokio::spawn(async move {
+                read("/var/spawned").await;
+            });
+            refresh.await;
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects: Vec<_> = analyze(&ast, &HashMap::new()).iter().map(ToString::to_string).collect();
+        assert_eq!(side_effects, vec![
+            r#"reads_file("/etc/app.toml")"#,
+            r#"reads_file("/var/spawned")"#,
+            r#"reads_file("/var/cache")"#,
+        ]);
+    }
+
+    #[test]
+    fn hole_origins() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let path = std::env::args().nth(1).unwrap();
+            read(&path);
+
+            let mut home = std::env::var("HOME").unwrap();
+            home.push_str("/.config");
+            read(&home);
+
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).unwrap();
+            read(&line);
+
+            read(dirs::config_dir());
+            read(format!("{}", path));
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let origins: Vec<_> = side_effects.iter().map(|side_effect| side_effect.hole_origins()).collect();
+        assert_eq!(origins, vec![
+            vec![&HoleOrigin::Argv],
+            vec![],
+            vec![&HoleOrigin::EnvVar(Some("HOME".to_string()))],
+            vec![&HoleOrigin::Stdin],
+            vec![&HoleOrigin::UnresolvedCallee("dirs::config_dir".to_string())],
+            vec![&HoleOrigin::Unsupported { syntax: "expression".to_string(), line: 24, column: 18 }],
+        ]);
+        assert_eq!(side_effects[1].to_string(), r#"reads_env("HOME")"#);
+        assert_eq!(side_effects[2].to_string(), r#"reads_file("*/.config")"#);
+    }
+
+    #[test]
+    fn environment_variable_reads() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        fn config_dir() -> String {
+            let mut dir = std::env::var("HOME").unwrap();
+            dir.push_str("/.config");
+            dir
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            read(&config_dir());
+            let name = "APP_DATA";
+            read(&std::env::var(name).unwrap());
+            for (key, value) in std::env::vars() {}
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let described: Vec<_> = side_effects.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            r#"reads_env("HOME")"#,
+            r#"reads_file("*/.config")"#,
+            r#"reads_env("APP_DATA")"#,
+            r#"reads_file("*")"#,
+            r#"reads_env("*")"#,
+        ]);
+        assert_eq!(side_effects[0].call_path, vec!["main", "config_dir", "std::env::var"]);
+        assert_eq!(side_effects[3].hole_origins(), vec![&HoleOrigin::EnvVar(Some("APP_DATA".to_string()))]);
+        assert_eq!(env_vars_read(&side_effects), vec!["HOME", "APP_DATA", "*"]);
+
+        let assumed_env = HashMap::from([("HOME".to_string(), "/home/svc".to_string())]);
+        let side_effects = analyze(&ast, &assumed_env);
+        assert_eq!(side_effects[0].to_string(), r#"reads_env("HOME")"#);
+        assert_eq!(side_effects[1].to_string(), r#"reads_file("/home/svc/.config")"#);
+        assert_eq!(side_effects[3].to_string(), r#"reads_file("*")"#);
+    }
+
+    #[test]
+    fn effect_registry() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(read_file(P)),
+        )]
+        fn read(p: &str) {}
+
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_cert(P)),
+        )]
+        fn load_cert(p: &str) {}
+
+        struct Tunnel;
+
+        impl Tunnel {
+            #[effect::declare(
+                args=(host as H),
+                side_effects=(connect(H, 443, "tcp", 1), opens_tunnel(H)),
+            )]
+            fn open(host: &str) {}
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            load_cert("/etc/ssl/app.pem");
+            Tunnel::open("10.0.0.1:443");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let mut registry = EffectRegistry::builtin();
+        let warnings: Vec<_> = effects::check_declarations(&ast, &registry).iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, vec![
+            "6:12: in `read`: unknown effect `read_file`, did you mean `reads_file`?",
+            "12:12: in `load_cert`: unknown effect `reads_cert`",
+            "21:16: in `open`: `connect` takes 1 to 3 arguments, found 4: `connect(address, port?, protocol?) => network(connect)`",
+            "21:16: in `open`: unknown effect `opens_tunnel`",
+        ]);
+
+        let side_effects = analyze(&ast, &HashMap::new());
+        let apparmor = policy::generate(PolicyEngine::Apparmor, PolicyOptions::default(), &registry, &side_effects);
+        assert!(!apparmor.contains("app.pem"));
+
+        registry.extend(EffectRegistry::parse("\
+            reads_cert(path) => file(r)\n\
+            opens_tunnel(address) => network(connect)\n\
+        ").unwrap());
+        assert_eq!(effects::check_declarations(&ast, &registry).len(), 2);
+        let apparmor = policy::generate(PolicyEngine::Apparmor, PolicyOptions::default(), &registry, &side_effects);
+        assert!(apparmor.contains("  /etc/ssl/app.pem r,\n"));
+        let network = network::network_effects(&side_effects, &registry);
+        assert_eq!(network[1].side_effect.name, "opens_tunnel");
+        assert_eq!(network[1].kind, network::NetworkEffectKind::Connect);
+        assert_eq!(network[1].endpoints, vec![network::Endpoint { host: Some("10.0.0.1".to_string()), port: Some(443) }]);
+    }
+
+    #[test]
+    fn effect_definitions() {
+        let rust_code = r#"
+        effect::define!(reads_config(p: path) => reads_file(p), stat(p));
+        effect::define!(loads_app(dir: path) => reads_config(dir + "/app.toml"), reads_config(dir + "/../shared.toml"));
+        effect::define!(forever(p: path) => forever(p + "/"));
+        effect::define!(typo(p: path) => read_file(p), writes_file(P));
+
+        #[effect::declare(
+            args=(dir as D),
+            side_effects=(loads_app(D)),
+        )]
+        fn load(dir: &str) {}
+
+        #[effect::entrypoint]
+        fn main() {
+            load("/etc/app");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let definitions = Definitions::collect_from_ast(&ast);
+        let mut registry = EffectRegistry::builtin();
+        definitions.register(&mut registry);
+        assert!(effects::check_declarations(&ast, &registry).is_empty());
+        let warnings: Vec<_> = definitions.check(&registry).iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, vec![
+            "5:9: in `typo`: `P` is not a parameter of `typo`",
+            "5:9: in `typo`: unknown effect `read_file`, did you mean `reads_file`?",
+        ]);
+
+        // Reports keep the defined effect, policies get what it expands into
+        let side_effects = analyze(&ast, &HashMap::new());
+        assert_eq!(side_effects[0].to_string(), r#"loads_app("/etc/app")"#);
+        let mut expanded = definitions.expand(&side_effects);
+        paths::normalize_side_effects(&mut expanded, &PathContext::default(), &registry);
+        let described: Vec<_> = expanded.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            r#"reads_file("/etc/app/app.toml")"#,
+            r#"stat("/etc/app/app.toml")"#,
+            r#"reads_file("/etc/shared.toml")"#,
+            r#"stat("/etc/shared.toml")"#,
+        ]);
+        assert_eq!(expanded[0].call_path, vec!["main", "load", "loads_app", "reads_config"]);
+        let apparmor = policy::generate(PolicyEngine::Apparmor, PolicyOptions::default(), &registry, &expanded);
+        assert!(apparmor.contains("  # reads_file(\"/etc/shared.toml\") in main -> load -> loads_app -> reads_config\n  /etc/shared.toml r,\n"));
+
+        // A definition that expands into itself stops there
+        let forever = SideEffect {
+            name: "forever".to_string(),
+            arguments: vec![VariableState::value("/tmp".to_string())],
+            repeated: true,
+            imprecise: false,
+            call_path: vec!["main".to_string()],
+            call_sites: Vec::new(),
+            derivations: Vec::new(),
+        };
+        let expanded = definitions.expand(&[forever]);
+        assert_eq!(expanded.len(), 1);
+        assert_eq!(expanded[0].to_string(), r#"forever("/tmp/")"#);
+        assert_eq!(expanded[0].call_path, vec!["main", "forever"]);
+        assert!(expanded[0].repeated);
+    }
+
+    #[test]
+    fn unsupported_constructs() {
+        let rust_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn read(p: &str) {}
+
+        fn serve((host, port): (&str, u16)) {
+            read(host);
+        }
+
+        const CONFIG: &str = "/etc/app.toml";
+
+        #[effect::entrypoint]
+        fn main() {
+            let path;
+            path = "/tmp/a";
+            read(path);
+            read(CONFIG);
+            serve(("/srv", 80));
+            let handlers = [read];
+            handlers[0]("/tmp/b");
+            read(path as &str);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let (side_effects, gaps, _) = analyze_entrypoint(&ast, find_entrypoint(&ast, None).unwrap(), &HashMap::new(), &[]);
+        let described: Vec<_> = side_effects.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            r#"reads_file("/tmp/a")"#,
+            r#"reads_file("*")"#,
+            r#"reads_file("*")"#,
+            r#"reads_file("*")"#,
+        ]);
+        let described: Vec<_> = gaps.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            "13:13: unsupported uninitialized `let` in main",
+            "16:18: unsupported path `CONFIG` in main",
+            "19:13: unsupported call of an expression in main",
+            "20:18: unsupported expression in main",
+        ]);
+        assert_eq!(side_effects[1].hole_origins(), vec![&HoleOrigin::Unsupported {
+            syntax: "path `CONFIG`".to_string(),
+            line: 16,
+            column: 18,
+        }]);
+    }
+
+    #[test]
+    fn methods_on_unknown_receivers() {
+        let rust_code = r#"
+        #[effect::declare(args=(url as U), side_effects=(connect(U)))]
+        fn open(url: &str) -> Db {}
+
+        #[effect::entrypoint]
+        fn main() {
+            let db = open("10.0.0.1:5432");
+            db.drop_everything("users");
+            let name = std::env::args().nth(1).unwrap();
+            let name = name.trim().to_string();
+            name.remove_dir_all();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let (side_effects, gaps, _) = analyze_entrypoint(&ast, find_entrypoint(&ast, None).unwrap(), &HashMap::new(), &[]);
+        assert_eq!(side_effects.len(), 1);
+        // Gaps, so the report isn't sound
+        let described: Vec<_> = gaps.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            "8:16: unresolved call to `drop_everything` in main",
+            "11:18: unresolved call to `remove_dir_all` in main",
+        ]);
+    }
+
+    #[test]
+    fn modules_in_other_files() {
+        let rust_code = r#"
+        mod handlers;
+        #[cfg(test)]
+        mod tests;
+
+        mod util {
+            pub fn read(p: &str) {}
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            handlers::serve();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let (_, gaps, _) = analyze_entrypoint(&ast, find_entrypoint(&ast, None).unwrap(), &HashMap::new(), &[]);
+        let described: Vec<_> = gaps.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            "2:13: module `handlers` in another file, which isn't analyzed",
+            "12:13: call to `handlers::serve`, which has no declaration in main",
+        ]);
+    }
+
+    #[test]
+    fn gaps() {
+        let rust_code = r#"
+        use std::fs::remove_file;
+
+        extern "C" {
+            fn getpid() -> i32;
+        }
+
+        fn cleanup(p: &str) {
+            std::fs::remove_file(p);
+            remove_file(p);
+            crate::helpers::cleanup(p);
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let pid = unsafe { getpid() };
+            unsafe {
+                asm!("syscall");
+            }
+            cleanup("/tmp/a");
+            let f = |p| cleanup(p);
+            f("/tmp/b");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let (side_effects, gaps, _) = analyze_entrypoint(&ast, find_entrypoint(&ast, None).unwrap(), &HashMap::new(), &[]);
+        assert!(side_effects.is_empty());
+        let described: Vec<_> = gaps.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            "16:23: unsafe block in main",
+            "16:32: FFI call to `getpid` in main",
+            "17:13: unsafe block in main",
+            "18:17: inline assembly in main",
+            "9:13: call to `std::fs::remove_file`, which has no declaration in main -> cleanup",
+            "10:13: unresolved call to `remove_file` in main -> cleanup",
+            "11:13: unresolved call to `crate::helpers::cleanup` in main -> cleanup",
+        ]);
+
+        let categories: Vec<_> = gaps::by_category(&gaps).into_iter()
+            .map(|(category, gaps)| (category, gaps.len()))
+            .collect();
+        assert_eq!(categories, vec![
+            ("unsafe blocks", 2),
+            ("FFI calls", 1),
+            ("inline assembly", 1),
+            ("external functions without a declaration", 1),
+            ("unresolved callees", 2),
+        ]);
+    }
+
+    #[test]
+    fn recursion() {
+        let rust_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn read(p: &str) {}
+
+        fn walk(p: &str) {
+            read(p);
+            walk(p)
+        }
+
+        fn ping(p: &str) {
+            read(p);
+            pong(p);
+        }
+
+        fn pong(p: &str) {
+            ping(p);
+        }
+
+        struct Tree;
+
+        impl Tree {
+            fn visit(&self, p: &str) {
+                read(p);
+                self.visit(p);
+            }
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            walk("/srv/a");
+            ping("/srv/b");
+            let tree = Tree;
+            tree.visit("/srv/c");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let (side_effects, gaps, _) = analyze_entrypoint(&ast, find_entrypoint(&ast, None).unwrap(), &HashMap::new(), &[]);
+        let described: Vec<_> = side_effects.iter()
+            .map(|side_effect| format!("{} in {}", side_effect, side_effect.call_path.join(" -> ")))
+            .collect();
+        assert_eq!(described, vec![
+            r#"reads_file("/srv/a") in main -> walk -> read"#,
+            r#"reads_file("/srv/b") in main -> ping -> read"#,
+            r#"reads_file("/srv/c") in main -> Tree::visit -> read"#,
+        ]);
+        let described: Vec<_> = gaps.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            "7:13: recursive call to `walk` in main -> walk",
+            "16:13: recursive call to `ping` in main -> ping -> pong",
+            "24:22: recursive call to `Tree::visit` in main -> Tree::visit",
+        ]);
+    }
+
+    #[test]
+    fn provenance() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(a as A, b as B),
+            side_effects=(reads_file(A + '/' + B)),
+            returns=(A + '/' + B),
+        )]
+        fn fn_with_effects(a: &str, b: &str) -> String {}
+
+        effect::define!(reads_config(p: path) => reads_file(p + ".toml"));
+
+        #[effect::declare(args=(p as P), side_effects=(reads_config(P)))]
+        fn load(p: &str) {}
+
+        fn unknown(a: &str, b: &str) -> String {
+            let c = fn_with_effects(a, b);
+            fn_with_effects(&c, "config_file.json")
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let _uk = unknown("~", "config_dir");
+            let f = |p| load(p);
+            f("/etc/app");
+            std::process::Command::new("ls").output();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let sites = |side_effect: &SideEffect| -> Vec<String> {
+            assert_eq!(side_effect.call_sites.len(), side_effect.call_path.len());
+            side_effect.call_sites.iter().map(ToString::to_string).collect()
+        };
+        assert_eq!(side_effects[1].to_string(), r#"reads_file("~/config_dir/config_file.json")"#);
+        assert_eq!(side_effects[1].call_path, vec!["main", "unknown", "fn_with_effects"]);
+        assert_eq!(sites(&side_effects[1]), vec!["20:12", "21:23", "16:13"]);
+        let derivation = side_effects[1].derivation(0).unwrap();
+        assert_eq!(derivation.expression, "A + '/' + B");
+        let bindings: Vec<_> = derivation.bindings.iter().map(|(name, state)| format!("{} = {}", name, state)).collect();
+        assert_eq!(bindings, vec![r#"A = "~/config_dir""#, r#"B = "config_file.json""#]);
+
+        assert_eq!(side_effects[2].call_path, vec!["main", "<closure>", "load"]);
+        assert_eq!(sites(&side_effects[2]), vec!["20:12", "23:13", "22:25"]);
+        assert_eq!(side_effects[3].call_path, vec!["main", "Command::output"]);
+        assert_eq!(sites(&side_effects[3]), vec!["20:12", "24:46"]);
+        assert!(side_effects[3].derivation(0).is_none());
+
+        let definitions = Definitions::collect_from_ast(&ast);
+        let expanded = definitions.expand(&side_effects[2..3]);
+        assert_eq!(expanded[0].to_string(), r#"reads_file("/etc/app.toml")"#);
+        assert_eq!(sites(&expanded[0]), vec!["20:12", "23:13", "22:25", "9:9"]);
+        assert_eq!(expanded[0].derivation(0).unwrap().expression, "p + '.toml'");
+    }
+
+    #[test]
+    fn call_graph() {
+        let rust_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn read(p: &str) {}
+
+        fn load(dir: &str) {
+            read(dir);
+            for name in ["a", "b"] {
+                read(name);
+            }
+            std::fs::remove_file(dir);
+            missing(dir);
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            load("/etc/app");
+            let f = |p| read(p);
+            f("/tmp");
+            std::process::Command::new("ls").output();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let (side_effects, _, mut call_graph) = analyze_entrypoint(&ast, find_entrypoint(&ast, None).unwrap(), &HashMap::new(), &[]);
+        call_graph.annotate(&side_effects);
+        assert_eq!(call_graph.entrypoints, vec!["main"]);
+        let nodes: Vec<_> = call_graph.nodes.iter().map(|node| (node.name.as_str(), node.kind)).collect();
+        assert_eq!(nodes, vec![
+            ("main", CallKind::Inferred),
+            ("load", CallKind::Inferred),
+            ("read", CallKind::Declared),
+            ("std::fs::remove_file", CallKind::External),
+            ("missing", CallKind::Unresolved),
+            ("<closure>", CallKind::Inferred),
+            ("Command::output", CallKind::Declared),
+        ]);
+        let edges: Vec<_> = call_graph.edges.iter()
+            .map(|edge| format!("{} -> {}: {}", edge.caller, edge.callee, edge.side_effects.join(", ")))
+            .collect();
+        assert_eq!(edges, vec![
+            r#"main -> load: reads_file("/etc/app"), reads_file("a" | "b")"#,
+            r#"load -> read: reads_file("/etc/app"), reads_file("a" | "b")"#,
+            "load -> std::fs::remove_file: ",
+            "load -> missing: ",
+            r#"main -> <closure>: reads_file("/tmp")"#,
+            r#"<closure> -> read: reads_file("/tmp")"#,
+            r#"main -> Command::output: exec("ls")"#,
+        ]);
+
+        let dot = call_graph.to_dot();
+        assert!(dot.contains(r#""read" [kind=declared, shape=box];"#));
+        assert!(dot.contains(r#""main" -> "<closure>" [label="reads_file(\"/tmp\")"];"#));
+        let json: serde_json::Value = serde_json::from_str(&call_graph.to_json()).unwrap();
+        assert_eq!(json["nodes"][4], serde_json::json!({ "name": "missing", "kind": "unresolved" }));
+        assert_eq!(json["edges"][0]["side_effects"][0], r#"reads_file("/etc/app")"#);
+    }
+
+    #[test]
+    fn analyzer() {
+        let example_app = Path::new(env!("CARGO_MANIFEST_DIR")).join("../example-app");
+        let report = Analyzer::new(&example_app).assume_env("HOME", "/home/svc").run().unwrap();
+        assert!(report.source.ends_with("src/main.rs"));
+        let described: Vec<_> = report.side_effects.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            r#"reads_file("/home/svc/config_dir")"#,
+            r#"reads_file("/home/svc/config_dir/config_file.json")"#,
+        ]);
+        assert!(report.warnings.is_empty());
+        assert!(report.policy(PolicyEngine::Apparmor, PolicyOptions::default()).contains("/home/svc/config_dir r,"));
+
+        let report = Analyzer::new(&example_app).entrypoint("unknown").run().unwrap();
+        assert_eq!(report.side_effects[0].to_string(), r#"reads_file("*/*")"#);
+        assert_eq!(report.side_effects[0].call_path, vec!["unknown", "fn_with_effects"]);
+
+        let error = Analyzer::new(&example_app).entrypoint("missing").run().unwrap_err();
+        assert!(error.to_string().ends_with("no function named `missing`"));
+    }
+
+    #[test]
+    fn baseline() {
+        let effect = |name: &str, arguments: &[&[&str]], call_path: &[&str]| BaselineEffect {
+            name: name.to_string(),
+            arguments: arguments.iter().map(|globs| globs.iter().map(ToString::to_string).collect()).collect(),
+            call_path: call_path.iter().map(ToString::to_string).collect(),
+        };
+        let covers = |pattern: &str, glob: &str| {
+            effect("reads_file", &[&[pattern]], &[]).covers(&effect("reads_file", &[&[glob]], &["main"]))
+        };
+        assert!(covers("~/*.json", "~/a.json") && covers("~/*.json", "~/*/b.json") && covers("*", "~/*"));
+        assert!(!covers("~/*.json", "~/*") && !covers("~/a.json", "~/*.json"));
+        assert!(covers("~/[a-c].json", "~/b.json") && covers("~/[a-z][0-9]", "~/[ab][0-9]"));
+        assert!(!covers("~/[a-c].json", "~/d.json") && !covers("~/[^/]", "~/[ab]"));
+        assert!(covers("/srv/?", "/srv/[\\-x]") && !covers("/srv/?", "/srv/*"));
+        let both = effect("reads_file", &[&["/etc/app", "~/*"]], &[]);
+        assert!(both.covers(&effect("reads_file", &[&["~/a", "/etc/app"]], &[])));
+        assert!(!both.covers(&effect("writes_file", &[&["/etc/app"]], &[])));
+
+        let rust_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn read(p: &str) {}
+
+        #[effect::declare(args=(p as P), side_effects=(writes_file(P)))]
+        fn write(p: &str) {}
+
+        #[effect::entrypoint]
+        fn main() {
+            read("/etc/app/a.toml");
+            read(std::env::args().nth(1).unwrap());
+            write("/tmp/out");
+        }
+        "#;
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let mut baseline = Baseline::new(&[]);
+        baseline.side_effects = vec![
+            effect("reads_file", &[&["/etc/app/*.toml"]], &["main", "read"]),
+            effect("reads_file", &[&["/srv/data"]], &["main", "read"]),
+            effect("exec", &[&["ls"]], &["main", "Command::output"]),
+        ];
+        let changes = baseline.check(&side_effects);
+        assert_eq!(changes, vec![
+            BaselineChange::Broadened {
+                baseline: effect("reads_file", &[&["/etc/app/*.toml"]], &["main", "read"]),
+                current: effect("reads_file", &[&["*"]], &["main", "read"]),
+            },
+            BaselineChange::Added(effect("writes_file", &[&["/tmp/out"]], &["main", "write"])),
+            BaselineChange::Removed(effect("exec", &[&["ls"]], &["main", "Command::output"])),
+        ]);
+        assert!(changes[0].fails() && changes[1].fails() && !changes[2].fails());
+        assert!(Baseline::new(&side_effects).check(&side_effects).is_empty());
+
+        let literal_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn read(p: &str) {}
+
+        #[effect::entrypoint]
+        fn main() {
+            read("/srv/*");
+        }
+        "#;
+        let literal = analyze(&syn::parse_file(literal_code).unwrap(), &HashMap::new());
+        assert_eq!(Baseline::new(&literal).side_effects[0].arguments, vec![vec!["/srv/\\*"]]);
+        let hole = literal_code.replace(r#"read("/srv/*")"#, r#"read(&format!("/srv/{}", std::env::args().nth(1).unwrap()))"#);
+        let hole = analyze(&syn::parse_file(&hole).unwrap(), &HashMap::new());
+        assert!(matches!(Baseline::new(&literal).check(&hole).as_slice(), [BaselineChange::Broadened { .. }]));
+        assert!(covers("/srv/\\*", "/srv/\\*") && !covers("/srv/\\*", "/srv/*") && covers("/srv/*", "/srv/\\*"));
+    }
+
+    #[test]
+    fn sarif() {
+        let rust_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn read(p: &str) {}
+
+        #[effect::declare(args=(p as P), side_effects=(reads_fiel(P)))]
+        fn typo(p: &str) {}
+
+        #[effect::entrypoint]
+        fn main() {
+            let path = std::env::args().nth(1).unwrap();
+            read(&path);
+            unsafe {}
+        }
+        "#;
+        let source = std::env::temp_dir().join(format!("autobox-sarif-{}.rs", std::process::id()));
+        std::fs::write(&source, rust_code).unwrap();
+        let report = Analyzer::new(&source).registry(EffectRegistry::builtin()).run();
+        std::fs::remove_file(&source).unwrap();
+
+        let log: serde_json::Value = serde_json::from_str(&report.unwrap().sarif()).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        let results = run["results"].as_array().unwrap();
+        let rule_ids: Vec<_> = results.iter().map(|result| result["ruleId"].as_str().unwrap()).collect();
+        assert_eq!(rule_ids, vec!["side-effect", "declaration-mismatch", "tainted-argument", "unsafe-block"]);
+        for result in results {
+            let rule = &run["tool"]["driver"]["rules"][result["ruleIndex"].as_u64().unwrap() as usize];
+            assert_eq!(rule["id"], result["ruleId"]);
+            assert_eq!(rule["defaultConfiguration"]["level"], result["level"]);
+        }
+
+        // `read(&path)`, and the call chain from the entrypoint to it
+        let region = &results[0]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region, &serde_json::json!({ "startLine": 11, "startColumn": 13, "endLine": 11, "endColumn": 24 }));
+        assert_eq!(results[0]["relatedLocations"][1]["message"]["text"], "call to `read`");
+        // The name of `typo`
+        let region = &results[1]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region, &serde_json::json!({ "startLine": 6, "startColumn": 12, "endLine": 6, "endColumn": 16 }));
+        assert_eq!(results[2]["level"], "error");
+        assert!(results[2]["message"]["text"].as_str().unwrap().contains("from command line arguments"));
+        let region = &results[3]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region, &serde_json::json!({ "startLine": 12, "startColumn": 13, "endLine": 12, "endColumn": 19 }));
+    }
+
+    #[test]
+    fn config() {
+        assert_eq!(Config::parse(CONFIG_TEMPLATE).unwrap(), Config::default());
+        assert!(Config::parse("entrypoint = \"main\"").is_err());
+
+        let dir = std::env::temp_dir().join(format!("autobox-config-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("autobox")).unwrap();
+        std::fs::write(dir.join("main.rs"), r#"
+        #[effect::declare(args=(p as P), side_effects=(writes_file(P)))]
+        fn write(p: &str) {}
+
+        fn debug_dump() {
+            write("/tmp/dump");
+        }
+
+        fn main() {
+            debug_dump();
+            generated(std::env::var("HOME").unwrap());
+        }
+
+        fn worker() {
+            write("/srv/out");
+        }
+        "#).unwrap();
+        std::fs::write(dir.join("autobox/declarations.rs"), r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_file(P)))]
+        fn generated(p: String) {}
+        "#).unwrap();
+        std::fs::write(dir.join(DEFAULT_CONFIG), r#"
+        entrypoints = ["main", "worker"]
+        declarations = ["autobox/declarations.rs"]
+        ignore = ["debug_dump"]
+        format = "sarif"
+
+        [assume_env]
+        HOME = "/home/svc"
+
+        [generate]
+        policy_engine = "landlock"
+
+        [generate.apparmor]
+        exec_transition = "px"
+        "#).unwrap();
+
+        let found = Config::find(&dir.join("autobox")).unwrap();
+        assert_eq!(found, dir.canonicalize().unwrap().join(DEFAULT_CONFIG));
+        let config = Config::read(&found).unwrap();
+        assert_eq!(config.format, Some(ReportFormat::Sarif));
+        assert_eq!(config.generate.policy_engine, Some(PolicyEngine::Landlock));
+        assert_eq!(config.generate.apparmor.exec_transition, Some(ExecTransition::Px));
+        assert_eq!(config.generate.seccomp.exec_once, None);
+        let report = Analyzer::new(dir.join("main.rs")).registry(EffectRegistry::builtin()).config(&config).run();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let report = report.unwrap();
+        let described: Vec<_> = report.side_effects.iter()
+            .map(|side_effect| format!("{} in {}", side_effect, side_effect.call_path.join(" -> ")))
+            .collect();
+        assert_eq!(described, vec![
+            r#"reads_env("HOME") in main -> std::env::var"#,
+            r#"reads_file("/home/svc") in main -> generated"#,
+            r#"writes_file("/srv/out") in worker -> write"#,
+        ]);
+        assert_eq!(report.call_graph.entrypoints, vec!["main", "worker"]);
+        assert!(report.call_graph.node("debug_dump").is_none());
+    }
+
+    #[test]
+    fn declarations_locations() {
+        let dir = std::env::temp_dir().join(format!("autobox-declarations-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), r#"
+        #[effect::declare(args=(p as P), side_effects=(writes_file(P)))]
+        fn write(p: &str) {}
+
+        #[effect::entrypoint]
+        fn main() {
+            generated("/srv/in");
+            flush();
+        }
+        "#).unwrap();
+        std::fs::write(dir.join("declarations.rs"), r#"
+        #[effect::declare(args=(p as P), side_effects=(reads_socket(P)))]
+        fn generated(p: &str) {}
+
+        fn flush() {
+            write("/srv/out");
+            unsafe {}
+        }
+        "#).unwrap();
+        let report = Analyzer::new(dir.join("main.rs"))
+            .registry(EffectRegistry::builtin())
+            .declarations(dir.join("declarations.rs"))
+            .run();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let report = report.unwrap();
+        let declarations = Some(dir.join("declarations.rs"));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].file, declarations);
+        assert_eq!(report.warnings[0].line, 3);
+        assert_eq!(report.gaps.len(), 1);
+        assert_eq!(report.gaps[0].file, declarations);
+        assert_eq!(report.gaps[0].line, 7);
+        assert_eq!(report.file(&report.gaps[0].file), dir.join("declarations.rs"));
+        let call_sites = &report.side_effects[1].call_sites;
+        assert_eq!(call_sites.iter().map(|site| (&site.file, site.line)).collect::<Vec<_>>(), vec![
+            (&None, 6),
+            (&None, 8),
+            (&declarations, 6),
+        ]);
+
+        let log: serde_json::Value = serde_json::from_str(&report.sarif()).unwrap();
+        let uri = |result: &serde_json::Value| result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"].clone();
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        let declarations_uri = dir.join("declarations.rs").to_string_lossy().to_string();
+        let main_uri = dir.join("main.rs").to_string_lossy().to_string();
+        assert_eq!(results.iter().map(uri).collect::<Vec<_>>(), vec![
+            main_uri.as_str(),
+            declarations_uri.as_str(),
+            declarations_uri.as_str(),
+            declarations_uri.as_str(),
+        ]);
+        assert_eq!(results[1]["relatedLocations"][0]["physicalLocation"]["artifactLocation"]["uri"], main_uri.as_str());
+        assert_eq!(log["runs"][0]["artifacts"][1]["location"]["uri"], declarations_uri.as_str());
+    }
+
+    #[test]
+    fn cargo_metadata() {
+        let example_app = Path::new(env!("CARGO_MANIFEST_DIR")).join("../example-app");
+        let metadata = serde_json::json!({
+            "packages": [
+                {
+                    "id": "example-app 0.1.0 (path+file:///ws/example-app)",
+                    "name": "example-app",
+                    "manifest_path": example_app.join("Cargo.toml"),
+                    "targets": [
+                        { "name": "example-app", "kind": ["bin"], "src_path": example_app.join("src/main.rs") },
+                        { "name": "helpers", "kind": ["lib"], "src_path": example_app.join("src/lib.rs") },
+                    ],
+                },
+                {
+                    "id": "tools 0.1.0 (path+file:///ws/tools)",
+                    "name": "tools",
+                    "manifest_path": "/ws/tools/Cargo.toml",
+                    "targets": [
+                        { "name": "gen", "kind": ["bin"], "src_path": "/ws/tools/src/bin/gen.rs" },
+                        { "name": "bench", "kind": ["bench"], "src_path": "/ws/tools/benches/bench.rs" },
+                    ],
+                },
+            ],
+            "workspace_members": [
+                "tools 0.1.0 (path+file:///ws/tools)",
+                "example-app 0.1.0 (path+file:///ws/example-app)",
+            ],
+        });
+        let binaries = cargo::parse_metadata(&metadata.to_string()).unwrap();
+        assert_eq!(binaries, vec![
+            BinaryTarget {
+                package: "tools".to_string(),
+                name: "gen".to_string(),
+                src_path: PathBuf::from("/ws/tools/src/bin/gen.rs"),
+                package_dir: PathBuf::from("/ws/tools"),
+            },
+            BinaryTarget {
+                package: "example-app".to_string(),
+                name: "example-app".to_string(),
+                src_path: example_app.join("src/main.rs"),
+                package_dir: example_app.clone(),
+            },
+        ]);
+        assert!(binaries[0].has_entrypoint().is_err());
+        assert!(binaries[1].has_entrypoint().unwrap());
+
+        let report = binaries[1].analyzer().run().unwrap();
+        assert_eq!(report.source, example_app.join("src/main.rs"));
+        assert!(!report.side_effects.is_empty());
+    }
+
+    #[test]
+    fn path_normalization() {
+        let normalized = |path: VariableState, home: Option<&str>, working_dir: Option<&str>| {
+            let context = PathContext {
+                home: home.map(ToString::to_string),
+                working_dir: working_dir.map(ToString::to_string),
+            };
+            paths::normalize(&path, &context).to_string()
+        };
+        let value = |path: &str| VariableState::value(path.to_string());
+        assert_eq!(normalized(value("~/./a//b/../c"), None, None), r#""~/a/c""#);
+        assert_eq!(normalized(value("~/./a//b/../c"), Some("/home/svc"), None), r#""/home/svc/a/c""#);
+        assert_eq!(normalized(value("~/../x"), None, None), r#""~/../x""#);
+        assert_eq!(normalized(value("~user/x"), Some("/home/svc"), None), r#""~user/x""#);
+        assert_eq!(normalized(value("/../etc/./passwd"), None, None), r#""/etc/passwd""#);
+        assert_eq!(normalized(value("data/../../x"), None, None), r#""../x""#);
+        assert_eq!(normalized(value("data/../../x"), None, Some("/srv/app")), r#""/srv/x""#);
+        assert_eq!(normalized(value("./logs//"), None, Some("/srv/app")), r#""/srv/app/logs/""#);
+        assert_eq!(normalized(value("a/.."), None, None), r#"".""#);
+        // A hole could be any number of directories, so `..` after it stays
+        let hole = VariableState::joined(&[VariableState::hole(HoleOrigin::Argv), value("../x")], "/");
+        assert_eq!(normalized(hole.clone(), None, Some("/srv/app")), r#""*/../x""#);
+        let union = value("/etc/app.toml").join(&value("/etc/./app.toml")).join(&value("app.toml"));
+        assert_eq!(normalized(union, None, Some("/etc")), r#""/etc/app.toml""#);
+
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            read("~/config_dir");
+            let mut config = std::env::var("HOME").unwrap();
+            config.push_str("/.config/app.toml");
+            read(&config);
+            read("data/./cache.db");
+            std::process::Command::new("~/bin/../bin/tool").spawn().unwrap();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let registry = EffectRegistry::builtin();
+        let mut side_effects = analyze(&ast, &HashMap::new());
+        paths::normalize_side_effects(&mut side_effects, &PathContext::default(), &registry);
+        let apparmor = policy::generate(PolicyEngine::Apparmor, PolicyOptions::default(), &registry, &side_effects);
+        for rule in ["@{HOME}/config_dir r,", "@{HOME}/.config/app.toml r,", "/**/data/cache.db r,", "@{HOME}/bin/tool ix,"] {
+            assert!(apparmor.contains(&format!("  {}\n", rule)), "{}", rule);
+        }
+
+        let context = PathContext { home: Some("/home/svc".to_string()), working_dir: Some("/srv/app".to_string()) };
+        paths::normalize_side_effects(&mut side_effects, &context, &registry);
+        let described: Vec<_> = side_effects.iter().map(ToString::to_string).collect();
+        assert_eq!(described, vec![
+            r#"reads_file("/home/svc/config_dir")"#,
+            r#"reads_env("HOME")"#,
+            r#"reads_file("*/.config/app.toml")"#,
+            r#"reads_file("/srv/app/data/cache.db")"#,
+            r#"exec("/home/svc/bin/tool")"#,
+        ]);
+    }
+
+    #[test]
+    fn file_reads_are_side_effects() {
+        let rust_code = r#"
+        #[effect::entrypoint]
+        fn main() {
+            std::fs::read_to_string("/x").unwrap();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects: Vec<_> = analyze(&ast, &HashMap::new()).iter()
+            .map(|side_effect| format!("{} in {}", side_effect, side_effect.call_path.join(" -> ")))
+            .collect();
+        assert_eq!(side_effects, vec![r#"reads_file("/x") in main -> std::fs::read_to_string"#]);
+    }
+
+    #[test]
+    fn taint_from_sources_to_sinks() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        fn load(name: &str) {
+            let mut path = "/srv/data/".to_string();
+            path.push_str(name);
+            read(&path);
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let name = std::env::args().nth(1).unwrap();
+            load(&name);
+            let home = std::env::var("HOME").unwrap();
+            read(&home);
+            read("/etc/app.toml");
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let sinks = vec!["reads_file".to_string()];
+
+        let findings = taint::find_tainted(&side_effects, &[TaintSource::Args], &sinks);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].to_string(), [
+            r#"Tainted argument 0 of reads_file("/srv/data/*"): "/srv/data/*""#,
+            "    from command line arguments via `name` -> `name` in `load` -> `path` -> `p` in `read`",
+            "    called through main -> load -> read",
+        ].join("\n"));
+
+        let findings = taint::find_tainted(&side_effects, TaintSource::ALL, &sinks);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[1].sources, vec![&Taint {
+            origin: HoleOrigin::EnvVar(Some("HOME".to_string())),
+            flow: vec!["`home`".to_string(), "`p` in `read`".to_string()],
+        }]);
+
+        let findings = taint::find_tainted(&side_effects, TaintSource::ALL, &["exec".to_string()]);
+        assert!(findings.is_empty());
+
+        // A value that's assumed is still untrusted
+        let assumed_env = HashMap::from([("HOME".to_string(), "/home/svc".to_string())]);
+        let side_effects = analyze(&ast, &assumed_env);
+        let findings = taint::find_tainted(&side_effects, &[TaintSource::Env], &sinks);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].side_effect.to_string(), r#"reads_file("/home/svc")"#);
+        assert_eq!(findings[0].sources[0].to_string(), "environment variable HOME via `home` -> `p` in `read`");
+    }
+
+    #[test]
+    fn taint_through_declared_patterns() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(id as I: /[0-9]+/),
+            side_effects=(reads_file("/srv/" + I)),
+        )]
+        fn read_record(id: &str) {
+            std::fs::read_to_string(format!("/srv/{}", id)).unwrap();
+        }
+
+        fn record_id() -> String {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).unwrap();
+            line
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let id = record_id();
+            read_record(&id);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        // The hole is narrowed to the pattern, but the value is still from stdin
+        assert!(!side_effects[0].arguments[0].has_hole());
+        let findings = taint::find_tainted(&side_effects, &[TaintSource::Stdin], &["reads_file".to_string()]);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].sources[0].to_string(),
+            "standard input via `line` -> returned by `record_id` -> `id` -> `id` in `read_record`",
+        );
+    }
+
+    #[test]
+    fn declared_patterns() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(id as I: /[0-9]+/, name as N: /[^/]{1,8}/),
+            side_effects=(reads_file("/srv/" + I + "/" + N)),
+        )]
+        fn read_record(id: &str, name: &str) {
+            std::fs::read_to_string(format!("/srv/{}/{}", id, name)).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let id = std::env::args().nth(1).unwrap();
+            read_record(&id, "notes");
+            for short in ["ab", "cd"] {
+                read_record("42", short);
+            }
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        assert_eq!(side_effects[0].to_string(), r#"reads_file("/srv/*/notes")"#);
+        // The class still knows the `*` came from the command line
+        assert_eq!(side_effects[0].arguments[0].alternatives[0].constraints[1], VariableStateConstraint::Class(
+            CharClass::new(vec![('0', '9')], 1, None),
+            vec![HoleOrigin::Argv],
+        ));
+        assert_eq!(side_effects[0].arguments[0].hole_origins(), vec![&HoleOrigin::Argv]);
+        assert!(side_effects[1].arguments[0].hole_origins().is_empty());
+        assert_eq!(side_effects[1].to_string(), r#"reads_file("/srv/42/ab" | "/srv/42/cd")"#);
+
+        let (_, pattern) = Pattern::parse("/[^\\/]{1,8}/").unwrap();
+        let state = VariableState::from_pattern(&pattern, &[]);
+        assert_eq!(globholes(&state), vec!["*"]);
+        let constraints = &state.alternatives[0].constraints;
+        assert_eq!(constraints, &vec![VariableStateConstraint::Class(CharClass::new(vec![('\0', '.'), ('0', char::MAX)], 1, Some(8)), Vec::new())]);
+        if let VariableStateConstraint::Class(ref class, _) = constraints[0] {
+            assert_eq!(class.to_string(), "[^/]{1,8}");
+        }
+    }
+
+    #[test]
+    fn integers_and_socket_addresses() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(addr as A),
+            side_effects=(binds(A)),
+        )]
+        fn listen(addr: (&str, u16)) {
+            std::net::TcpListener::bind(addr).unwrap();
+        }
+
+        #[effect::declare(
+            args=(host as H, port as P),
+            side_effects=(connect((H, P + 1)), connect(H, P * 2 - 1)),
+        )]
+        fn connect(host: &str, port: u16) {
+            std::net::TcpStream::connect((host, port + 1)).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let base = 8000;
+            let port = base + 80;
+            listen(("0.0.0.0", port));
+            let addr = ("127.0.0.1", 9000 - 1);
+            listen(("localhost", addr.1));
+            listen(std::net::SocketAddr::from(([10, 0, 0, 1], port % 100)));
+            listen(SocketAddr::new(Ipv4Addr::new(192, 168, 0, 1).into(), -(-443)));
+            for p in [80, 443] {
+                connect("example.com", p);
+            }
+            let unknown: u16 = std::env::args().nth(1).unwrap().parse().unwrap();
+            listen(("0.0.0.0", unknown + 1));
+            let flag = true;
+            listen((flag, 1u16 << 2));
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let side_effects: Vec<_> = side_effects.iter().map(|side_effect| side_effect.to_string()).collect();
+        assert_eq!(side_effects, vec![
+            r#"binds("0.0.0.0:8080")"#,
+            r#"binds("localhost:8999")"#,
+            r#"binds("10.0.0.1:80")"#,
+            r#"binds("192.168.0.1:443")"#,
+            r#"connect("example.com:81" | "example.com:444")"#,
+            r#"connect("example.com", "159" | "885")"#,
+            r#"binds("0.0.0.0:*")"#,
+            r#"binds("true:*")"#,
+        ]);
+
+        let port = VariableState::integer(80, "u16").join(&VariableState::integer(443, "u16"));
+        assert_eq!(port.integers(), Some(vec![80, 443]));
+        let addr = VariableState::tuple(vec![VariableState::value("::1".to_string()), port]);
+        assert_eq!(addr.field(1).to_string(), r#""80" | "443""#);
+        assert_eq!(addr.field(2).to_string(), r#""*""#);
+    }
+
+    #[test]
+    fn network_effects_and_policies() {
+        let rust_code = r#"
+        use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+
+        fn fetch(host: &str) {
+            let stream = TcpStream::connect((host, 443)).unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let listener = TcpListener::bind(("0.0.0.0", 8000 + 80)).unwrap();
+            fetch("10.0.0.1");
+            fetch("example.com");
+            let socket = UdpSocket::bind("[::1]:0").unwrap();
+            socket.send_to(&[1, 2], ("::1", 9000)).unwrap();
+            let port: u16 = std::env::args().nth(1).unwrap().parse().unwrap();
+            TcpStream::connect(("10.0.0.2", port)).unwrap();
+            for addr in ("localhost", 80).to_socket_addrs().unwrap() {}
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let registry = EffectRegistry::builtin();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let described: Vec<_> = side_effects.iter().map(|side_effect| side_effect.to_string()).collect();
+        assert_eq!(described, vec![
+            r#"bind("0.0.0.0:8080")"#,
+            r#"listen("0.0.0.0:8080")"#,
+            r#"connect("10.0.0.1:443")"#,
+            r#"connect("example.com:443")"#,
+            r#"bind("[::1]:0", "udp")"#,
+            r#"send_to("::1:9000")"#,
+            r#"connect("10.0.0.2:*")"#,
+            r#"resolve("localhost:80")"#,
+        ]);
+        assert_eq!(side_effects[3].call_path, vec!["main", "fetch", "TcpStream::connect"]);
+
+        let network = network::network_effects(&side_effects, &registry);
+        assert_eq!(network[4].protocol, network::Protocol::Udp);
+        assert_eq!(network[5].endpoints, vec![network::Endpoint { host: Some("::1".to_string()), port: Some(9000) }]);
+        assert_eq!(network[6].endpoints, vec![network::Endpoint { host: Some("10.0.0.2".to_string()), port: None }]);
+        assert!(network[3].resolves() && !network[2].resolves());
+
+        let seccomp = policy::generate(PolicyEngine::Seccomp, PolicyOptions::default(), &registry, &side_effects);
+        assert!(seccomp.contains("\nsocket: arg0 == AF_INET || arg0 == AF_INET6; return EPERM\n"));
+        assert!(seccomp.contains("\naccept4: 1\n"));
+
+        let landlock = policy::generate(PolicyEngine::Landlock, PolicyOptions::default(), &registry, &side_effects);
+        assert!(landlock.contains("// ConnectTcp isn't restricted, connect(\"10.0.0.2:*\") in main -> TcpStream::connect uses an unknown port\n"));
+        assert!(landlock.ends_with(&[
+            "Ruleset::default()",
+            "    .handle_access(AccessNet::BindTcp)?",
+            "    .create()?",
+            "    // bind(\"0.0.0.0:8080\") in main -> TcpListener::bind",
+            "    .add_rule(NetPort::new(8080, AccessNet::BindTcp))?",
+            "    .restrict_self()?;\n",
+        ].join("\n")));
+
+        let nftables = policy::generate(PolicyEngine::Nftables, PolicyOptions::default(), &registry, &side_effects);
+        for rule in ["ip daddr 10.0.0.1 tcp dport 443 accept", "ip6 daddr ::1 udp dport 9000 accept", "udp dport 53 accept",
+            "ip daddr 10.0.0.2 meta l4proto tcp accept", "tcp dport 8080 accept"] {
+            assert!(nftables.contains(&format!("        {}\n", rule)), "{}", rule);
+        }
+        assert!(!nftables.contains("dport 0 "));
+
+        let no_network = policy::generate(PolicyEngine::Seccomp, PolicyOptions::default(), &registry, &[]);
+        assert!(no_network.contains("# There are no network side effects, so no sockets are allowed\n"));
+
+        let apparmor = policy::generate(PolicyEngine::Apparmor, PolicyOptions::default(), &registry, &side_effects);
+        for rule in ["network inet stream,", "network inet6 dgram,", "network inet6 stream,"] {
+            assert!(apparmor.contains(&format!("  {}\n", rule)), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn crate_methods_named_like_network_methods() {
+        let rust_code = r#"
+        #[effect::declare(args=(p as P), side_effects=(writes_file(P)))]
+        fn write(p: &str) {}
+
+        struct Outbox;
+
+        impl Outbox {
+            fn send_to(&self, message: &str, to: &str) {
+                write(to);
+            }
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let outbox = Outbox;
+            outbox.send_to("hello", "/var/spool/outbox");
+            let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+            socket.send_to(&[1, 2], "10.0.0.1:53").unwrap();
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let described: Vec<_> = side_effects.iter()
+            .map(|side_effect| format!("{} in {}", side_effect, side_effect.call_path.join(" -> ")))
+            .collect();
+        assert_eq!(described, vec![
+            r#"writes_file("/var/spool/outbox") in main -> Outbox::send_to -> write"#,
+            r#"bind("0.0.0.0:0", "udp") in main -> UdpSocket::bind"#,
+            r#"send_to("10.0.0.1:53") in main -> send_to"#,
+        ]);
+    }
+
+    #[test]
+    fn process_execution_and_exec_policies() {
+        let rust_code = r#"
+        use std::process::Command;
+
+        fn git(args: &[&str]) {
+            Command::new("git").args(args).output().unwrap();
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let mut cmd = Command::new("/usr/bin/convert");
+            cmd.arg("-resize").arg("50%");
+            cmd.env("LANG", "C").args(["in.png", "out.png"]);
+            cmd.status().unwrap();
+            git(&["status"]);
+            Command::new("./bin/tool").current_dir("/opt/app").spawn().unwrap();
+            let program = std::env::args().nth(1).unwrap();
+            Command::new(program).spawn().unwrap();
+            for p in ["/usr/bin/a", "/usr/bin/b"] {
+                Command::new(p).status();
+            }
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let registry = EffectRegistry::builtin();
+        let side_effects = analyze(&ast, &HashMap::new());
+        let described: Vec<_> = side_effects.iter().map(|side_effect| side_effect.to_string()).collect();
+        assert_eq!(described, vec![
+            r#"exec("/usr/bin/convert", "-resize", "50%", "in.png", "out.png")"#,
+            r#"exec("git", "status")"#,
+            r#"exec("/opt/app/bin/tool")"#,
+            r#"exec("*")"#,
+            r#"exec("/usr/bin/a" | "/usr/bin/b")"#,
+        ]);
+        assert_eq!(side_effects[1].call_path, vec!["main", "git", "Command::output"]);
+        assert_eq!(side_effects[3].hole_origins(), vec![&HoleOrigin::Argv]);
+
+        let apparmor = policy::generate(PolicyEngine::Apparmor, PolicyOptions::default(), &registry, &side_effects);
+        for rule in ["/usr/bin/convert ix,", "/{,usr/}{,local/}{,s}bin/git ix,", "/opt/app/bin/tool ix,", "/** ix,",
+            "/usr/bin/a ix,", "/usr/bin/b ix,"] {
+            assert!(apparmor.contains(&format!("  {}\n", rule)), "{}", rule);
+        }
+        let options = PolicyOptions { exec_transition: ExecTransition::Px, exec_once: false };
+        assert!(policy::generate(PolicyEngine::Apparmor, options, &registry, &side_effects).contains("  /usr/bin/convert px,\n"));
+
+        let seccomp = policy::generate(PolicyEngine::Seccomp, PolicyOptions::default(), &registry, &side_effects);
+        assert!(seccomp.contains("\nexecve: 1\n") && seccomp.contains("\nclone: 1\n"));
+        let no_exec = policy::generate(PolicyEngine::Seccomp, PolicyOptions::default(), &registry, &[]);
+        assert!(!no_exec.contains("execve: 1"));
+        let options = PolicyOptions { exec_transition: ExecTransition::Ix, exec_once: true };
+        let exec_once = policy::generate(PolicyEngine::Seccomp, options, &registry, &[]);
+        assert!(exec_once.ends_with("\nexecve: 1\n") && !exec_once.contains("clone"));
+    }
+
+    #[test]
+    fn declared_unions() {
+        let rust_code = r#"
+        #[effect::declare(
+            args=(p as P),
+            side_effects=(reads_file(P)),
+        )]
+        fn read(p: &str) {
+            std::fs::read_to_string(p).unwrap();
+        }
+
+        #[effect::declare(
+            args=(home as H),
+            returns=("/etc/app.toml" | H + "/.app.toml"),
+        )]
+        fn config_path(home: &str) -> String {
+            unimplemented!()
+        }
+
+        #[effect::entrypoint]
+        fn main() {
+            let mut path = config_path("/home/user");
+            path.push_str(".bak");
+            read(&path);
+        }
+        "#;
+
+        let ast = syn::parse_file(rust_code).unwrap();
+        let side_effects = analyze(&ast, &HashMap::new());
+        assert_eq!(side_effects.len(), 1);
+        assert_eq!(globholes(&side_effects[0].arguments[0]), vec!["/etc/app.toml.bak", "/home/user/.app.toml.bak"]);
+        assert_eq!(side_effects[0].to_string(), r#"reads_file("/etc/app.toml.bak" | "/home/user/.app.toml.bak")"#);
+    }
+
+    #[test]
+    fn join_variable_states() {
+        let value = |value: &str| Alternative {
+            constraints: vec![VariableStateConstraint::Value(value.to_string())],
+        };
+
+        // Every alternative is kept
+        let joined = VariableState::value("~/a.json".to_string()).join(&VariableState::value("~/b.json".to_string()));
+        assert_eq!(globholes(&joined), vec!["~/a.json", "~/b.json"]);
+        assert_eq!(joined.to_string(), r#""~/a.json" | "~/b.json""#);
+
+        // Until there are too many of them
+        let many = (0..=MAX_ALTERNATIVES).fold(VariableState::value("~/0.json".to_string()), |joined, i| {
+            joined.join(&VariableState::value(format!("~/{}.json", i)))
+        });
+        assert_eq!(globholes(&many), vec!["~/[0-8].json"]);
+
+        // A single alternative covering both keeps their common prefix and suffix
+        assert_eq!(value("~/a.json").join(&value("~/b.json")).glob(), "~/[ab].json");
+        let joined = value("~").join(&value("~/sub"));
+        assert_eq!(joined.glob(), "~*");
+        assert_eq!(joined.constraints[1], VariableStateConstraint::Class(CharClass::new(vec![('/', '/'), ('b', 'b'), ('s', 's'), ('u', 'u')], 0, Some(4)), Vec::new()));
+
+        // Alternatives covered by another one are dropped
+        let covering = Alternative {
+            constraints: vec![
+                VariableStateConstraint::Value("~".to_string()),
+                VariableStateConstraint::Hole(HoleOrigin::Widened(Vec::new())),
+            ],
+        };
+        let covered = Alternative {
+            constraints: vec![
+                VariableStateConstraint::Value("~".to_string()),
+                VariableStateConstraint::Hole(HoleOrigin::Widened(Vec::new())),
+                VariableStateConstraint::Value("/sub".to_string()),
+            ],
+        };
+        assert_eq!(covering.join(&covered), covering);
+        let covering = VariableState::from_alternative(covering);
+        assert_eq!(VariableState::from_alternative(covered).join(&covering), covering);
+
+        // Growing values lose their upper bound, and then stop changing
+        let grown = VariableState::value("~x".to_string()).widen(&VariableState::value("~xx".to_string()));
+        assert_eq!(globholes(&grown), vec!["~x*"]);
+        let mut grown_again = grown.clone();
+        grown_again.concat(&VariableState::value("x".to_string()));
+        assert_eq!(grown.widen(&grown_again), grown);
+
+        let from_args = VariableState::hole(HoleOrigin::Argv);
+        let joined = VariableState::value("a".to_string()).join(&from_args);
+        assert_eq!(joined.hole_origins(), vec![&HoleOrigin::Argv]);
+
+        let same = VariableState::value("foo".to_string());
+        assert_eq!(same.join(&same), same);
+    }
+}
+
+/// An effect reachable from the entrypoint, ie: `reads_file("/etc/app.toml")`
+#[derive(Debug, Clone)]
+pub struct SideEffect {
+    /// The name of the effect in the registry, ie: `reads_file`
+    pub name: String,
+    pub arguments: Vec<VariableState>,
+    /// Whether this happens in a loop or iterator, and so may happen any number of times
+    pub repeated: bool,
+    /// Whether this comes from a `dyn Trait` call that could be any implementor of the trait
+    pub imprecise: bool,
+    /// The calls made from the entrypoint to reach this effect, ending with the function that declares it
+    pub call_path: Vec<String>,
+    /// Where each function in the call path is called, starting with where the entrypoint is defined
+    pub call_sites: Vec<Location>,
+    /// How each argument was derived, when it comes from a declaration or a definition
+    pub derivations: Vec<Option<Derivation>>,
+}
+
+/// A line and column of the analyzed file or a declarations file, and where the syntax there ends.
+/// Columns start at 1 and the end column is just past the last character
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The declarations file the syntax is in, or `None` for the analyzed file
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Location {
+    fn of(node: &impl syn::spanned::Spanned) -> Self {
+        let span = node.span();
+        Location {
+            file: declarations_file(span),
+            line: span.start().line,
+            column: span.start().column + 1,
+            end_line: span.end().line,
+            end_column: span.end().column + 1,
+        }
+    }
+}
+
+thread_local! {
+    /// Each declarations file parsed on this thread, with the span of its first item. Spans from
+    /// different files can't be joined, which is how the file of a node is found
+    static DECLARATIONS_FILES: RefCell<Vec<(PathBuf, proc_macro2::Span)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Read the declarations file at `path`, remembering which file its items come from so that their
+/// locations can point at it once they're added to the analyzed file
+fn read_declarations(path: &Path) -> Result<syn::File> {
+    let ast = read_ast(path)?;
+    if let Some(item) = ast.items.first() {
+        let span = syn::spanned::Spanned::span(item);
+        DECLARATIONS_FILES.with(|files| files.borrow_mut().push((path.to_path_buf(), span)));
+    }
+    Ok(ast)
+}
+
+/// The declarations file `span` is in, or `None` when it's in the analyzed file
+fn declarations_file(span: proc_macro2::Span) -> Option<PathBuf> {
+    DECLARATIONS_FILES.with(|files| {
+        files.borrow().iter()
+            .find(|(_, file_span)| span.join(*file_span).is_some())
+            .map(|(path, _)| path.clone())
+    })
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The expression an argument was declared as, and the values of the variables in it, ie:
+/// `A + '/' + B` where `A` is `"~"` and `B` is `"config_dir"`
+#[derive(Debug, Clone)]
+pub struct Derivation {
+    pub expression: String,
+    pub bindings: Vec<(String, VariableState)>,
+}
+
+impl Derivation {
+    fn of(expr: &Expr, arguments: &HashMap<&str, &VariableState>) -> Self {
+        let mut bindings: Vec<(String, VariableState)> = Vec::new();
+        for name in expr.variables() {
+            if let Some(state) = arguments.get(name) {
+                if !bindings.iter().any(|(bound, _)| bound == name) {
+                    bindings.push((name.to_string(), (*state).clone()));
+                }
+            }
+        }
+        Derivation {
+            expression: expr.to_string(),
+            bindings,
+        }
+    }
+}
+
+impl SideEffect {
+    /// How the argument at `index` was derived, if it was declared
+    pub fn derivation(&self, index: usize) -> Option<&Derivation> {
+        self.derivations.get(index).and_then(Option::as_ref)
+    }
+
+    /// Where the holes in any of the arguments came from
+    pub fn hole_origins(&self) -> Vec<&HoleOrigin> {
+        let mut origins = Vec::new();
+        for origin in self.arguments.iter().flat_map(VariableState::hole_origins) {
+            if !origins.contains(&origin) {
+                origins.push(origin);
+            }
+        }
+        origins
+    }
+
+    /// Classes in the arguments that are too broad to be anything but `*` in a glob, ie: `[^/]*`
+    pub fn broad_classes(&self) -> Vec<&CharClass> {
+        self.arguments.iter()
+            .flat_map(|arg| arg.alternatives.iter())
+            .flat_map(|alternative| alternative.constraints.iter())
+            .filter_map(|constraint| match constraint {
+                VariableStateConstraint::Class(class, _) if class.glob() == "*" => Some(class),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Display for SideEffect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, arg) in self.arguments.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// What a call to a function evaluated to from the perspective of its caller
+#[derive(Debug, Clone)]
+struct EvaluatedFn {
+    /// The state of the value returned by the function
+    returns: VariableState,
+    /// The states of arguments that were mutated through `&mut`, keyed by argument position
+    mutated_arguments: BTreeMap<usize, VariableState>,
+}
+
+// Given a function that does not been marked with `declare`, infer what the DeclareMacro output
+// would have looked like by inspecting its body
+// `self_ident` is what `self` is bound to for methods
+fn infer_fn<'a>(
+    side_effects: &mut Vec<SideEffect>,
+    sig: &'a syn::Signature,
+    block: &'a syn::Block,
+    self_ident: Option<&'a Ident>,
+    fn_arguments: &[VariableState],
+    items: &'a CrateItems<'a>,
+) -> EvaluatedFn {
+    let mut variables = BTreeMap::new();
+    let mut args = Vec::with_capacity(sig.inputs.len());
+    let mut mut_args = Vec::new();
+    for (arg_i, fn_arg) in sig.inputs.iter().enumerate() {
+        let (arg_name, mutable) = match fn_arg {
+            syn::FnArg::Typed(arg) => match &*arg.pat {
+                syn::Pat::Ident(ident) => {
+                    let mutable = matches!(&*arg.ty, syn::Type::Reference(syn::TypeReference { mutability: Some(_), .. }));
+                    (&ident.ident, mutable)
+                },
+                // (host, port) in `fn serve((host, port): (&str, u16))`
+                pat => {
+                    let state = fn_arguments.get(arg_i).cloned().unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked));
+                    bind_pattern(pat, 0, &state, &mut variables);
+                    continue;
+                }
+            },
+            syn::FnArg::Receiver(receiver) => match self_ident {
+                Some(self_ident) => (self_ident, receiver.reference.is_some() && receiver.mutability.is_some()),
+                None => continue,
+            },
+        };
+        let mut state = fn_arguments.get(arg_i).cloned()
+            .unwrap_or_else(|| VariableState::hole(HoleOrigin::Parameter(arg_name.to_string())));
+        state.flows_through(format!("`{}` in `{}`", arg_name, sig.ident));
+        // The caller knows best, but `impl Trait` and `&dyn Trait` at least tell us the trait
+        if let syn::FnArg::Typed(arg) = fn_arg {
+            if state.value_type.is_none() {
+                state.value_type = value_type_of(&arg.ty, &sig.generics);
+            }
+        }
+        args.push(Arg {
+            arg_name: Cow::Owned(arg_name.to_string()),
+            arg_binding: "",
+            pattern: None,
+        });
+        if mutable {
+            mut_args.push((arg_i, arg_name));
+        }
+        variables.insert((0, arg_name), VariableMetadata::new(
+            Some(arg_name),
+            0,
+            state,
+        ));
+    }
+
+    let mut index = 0;
+    let returns = evaluate_block(
+        &block.stmts,
+        side_effects,
+        &mut index,
+        &mut variables, items,
+    );
+
+    // Whatever state a `&mut` argument ends up in is visible to the caller
+    let mutated_arguments = mut_args
+        .into_iter()
+        .filter_map(|(arg_i, arg_name)| {
+            let var = find_binding(arg_name, 0, &variables)?;
+            Some((arg_i, var.variable_state.clone()))
+        })
+        .collect();
+
+    let mut returns = returns.unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked));
+    returns.flows_through(format!("returned by `{}`", sig.ident));
+    if returns.value_type.is_none() {
+        returns.value_type = return_type_of(sig);
+    }
+    EvaluatedFn {
+        returns,
+        mutated_arguments,
+    }
+}
+
+// Evaluate each statement of a block in order, returning the state of its trailing expression.
+// Every statement gets its own index, so `index` is left past the last statement of the block
+fn evaluate_block<'a>(
+    stmts: &'a [Stmt],
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    items: &'a CrateItems<'a>,
+) -> Option<VariableState> {
+    let mut returns = None;
+    for (i, statement) in stmts.iter().enumerate() {
+        *index += 1;  // dont put > 2^16 statements in your code!!!
+
+        match statement {
+            Stmt::Local(local) => {
+                let variable_state = match local.init {
+                    Some((_, ref init)) => get_variable_state(init, side_effects, index, variables, items),
+                    // `let x;` assigned later, which we'd have to follow through every branch
+                    None => VariableState::hole(items.unsupported("uninitialized `let`", local)),
+                };
+                // for now we only support single Ident bindings ie: `let x` but not `let Some(x)`
+                if let Some(var_name) = extract_variables_from_pat(&local.pat).first() {
+                    let mut variable_state = variable_state;
+                    variable_state.flows_through(format!("`{}`", var_name));
+                    variables.insert(
+                        (*index, var_name),
+                        VariableMetadata::new(*var_name, *index, variable_state),
+                    );
+                }
+            }
+            Stmt::Semi(expr, _) => {
+                get_variable_state(expr, side_effects, index, variables, items);
+            }
+            Stmt::Expr(expr) => {
+                let state = get_variable_state(expr, side_effects, index, variables, items);
+                // Loops without a trailing `;` are expression statements too, only the last one is returned
+                if i == stmts.len() - 1 {
+                    returns = Some(state);
+                }
+            }
+            Stmt::Item(_) => {}
+        }
+    }
+    returns
+}
+
+// Loops and closures passed to iterators run their body any number of times. We evaluate the body
+// until the variables it can change stop changing, widening them as we go, and then one last time
+// to collect its side effects, which are marked as possibly repeated
+fn evaluate_repeated<'a>(
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    mut body: impl FnMut(&mut Vec<SideEffect>, &mut u16, &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>),
+) {
+    let loop_index = *index;
+    let outer_variables = outer_bindings(loop_index, variables);
+
+    let mut entry = variables.clone();
+    let mut converged = false;
+    for _ in 0..MAX_LOOP_ITERATIONS {
+        *index = loop_index;
+        body(&mut Vec::new(), index, variables);
+
+        let mut changed = false;
+        for (var_name, instance) in &outer_variables {
+            let before = &find_binding(var_name, *instance, &entry).unwrap().variable_state;
+            let after = &find_binding(var_name, *instance, variables).unwrap().variable_state;
+            let joined = before.widen(after);
+            if joined != *before {
+                changed = true;
+                entry.insert((loop_index, var_name), VariableMetadata::new(*var_name, *instance, joined));
+            }
+        }
+        variables.clone_from(&entry);
+
+        if !changed {
+            converged = true;
+            break;
+        }
+    }
+
+    // Give up on anything that is still changing
+    if !converged {
+        *index = loop_index;
+        body(&mut Vec::new(), index, variables);
+        for (var_name, instance) in &outer_variables {
+            let before = &find_binding(var_name, *instance, &entry).unwrap().variable_state;
+            let after = &find_binding(var_name, *instance, variables).unwrap().variable_state;
+            if before != after {
+                let origin = HoleOrigin::widened(before.hole_origins().into_iter().chain(after.hole_origins()).cloned());
+                let mut widened = VariableState::hole(origin);
+                widened.add_taints(&before.taints);
+                widened.add_taints(&after.taints);
+                entry.insert((loop_index, var_name), VariableMetadata::new(*var_name, *instance, widened));
+            }
+        }
+        variables.clone_from(&entry);
+    }
+
+    *index = loop_index;
+    let mut repeated_side_effects = Vec::new();
+    body(&mut repeated_side_effects, index, variables);
+    side_effects.extend(repeated_side_effects.into_iter().map(|side_effect| SideEffect {
+        repeated: true,
+        ..side_effect
+    }));
+
+    // Anything declared in the body goes out of scope
+    variables.clone_from(&entry);
+}
+
+// `if` and `match` run one of their branches. Each branch is evaluated from the same variables, and
+// the variables of the enclosing scope end up in any of the states the branches left them in.
+// Returns the state of the value of whichever branch ran
+fn evaluate_branches<'a>(
+    branches: usize,
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    mut branch: impl FnMut(usize, &mut Vec<SideEffect>, &mut u16, &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>) -> VariableState,
+) -> VariableState {
+    let branch_index = *index;
+    let outer_variables = outer_bindings(branch_index, variables);
+
+    let entry = variables.clone();
+    let mut last_index = branch_index;
+    let mut returns: Option<VariableState> = None;
+    let mut joined: Vec<Option<VariableState>> = vec![None; outer_variables.len()];
+    for i in 0..branches {
+        *index = branch_index;
+        variables.clone_from(&entry);
+        let state = branch(i, side_effects, index, variables);
+        last_index = last_index.max(*index);
+        returns = Some(match returns {
+            Some(returns) => returns.join(&state),
+            None => state,
+        });
+        for ((var_name, instance), joined) in outer_variables.iter().zip(joined.iter_mut()) {
+            let after = &find_binding(var_name, *instance, variables).unwrap().variable_state;
+            *joined = Some(match joined {
+                Some(joined) => joined.join(after),
+                None => after.clone(),
+            });
+        }
+    }
+
+    // Anything declared in a branch goes out of scope
+    variables.clone_from(&entry);
+    *index = last_index + 1;
+    for ((var_name, instance), joined) in outer_variables.into_iter().zip(joined) {
+        let joined = match joined {
+            Some(joined) => joined,
+            None => continue,
+        };
+        if joined != find_binding(var_name, instance, &entry).unwrap().variable_state {
+            variables.insert((*index, var_name), VariableMetadata::new(var_name, instance, joined));
+        }
+    }
+    returns.unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked))
+}
+
+// The name and instance of every variable in scope at `index`, skipping those shadowed by another
+fn outer_bindings<'a>(
+    index: u16,
+    variables: &BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+) -> Vec<(&'a syn::Ident, u16)> {
+    let names: BTreeSet<&'a syn::Ident> = variables.keys().map(|(_, var_name)| *var_name).collect();
+    names.into_iter()
+        .filter_map(|var_name| Some((var_name, find_variable_metadata(var_name, index, variables)?.variable_instance_id)))
+        .collect()
+}
+
+// Bind every variable in a loop or closure pattern, ie: `p` in `for p in paths`
+fn bind_pattern<'a>(
+    pat: &'a syn::Pat,
+    index: u16,
+    variable_state: &VariableState,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+) {
+    let var_names = extract_variables_from_pat(pat);
+    // Destructured values can't be tracked, each of them could be anything
+    let variable_state = match var_names.len() {
+        1 => variable_state.clone(),
+        _ => VariableState::hole(HoleOrigin::Untracked),
+    };
+    for var_name in var_names {
+        variables.insert((index, var_name), VariableMetadata::new(var_name, index, variable_state.clone()));
+    }
+}
+
+// Evaluate the body of a closure passed to an iterator adapter, ie: `|p| std::fs::read(p)`, where
+// the last parameter is each of the `elements`. Returns the state of the closure's return value
+fn evaluate_closure<'a>(
+    closure: &'a syn::ExprClosure,
+    elements: &VariableState,
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    items: &'a CrateItems<'a>,
+) -> VariableState {
+    let mut returns = VariableState::hole(HoleOrigin::Untracked);
+    evaluate_repeated(side_effects, index, variables, |side_effects, index, variables| {
+        *index += 1;
+        for (i, input) in closure.inputs.iter().enumerate() {
+            // Anything before the item, like the accumulator of `fold`, is unknown
+            let state = if i == closure.inputs.len() - 1 { elements.clone() } else { VariableState::hole(HoleOrigin::Untracked) };
+            bind_pattern(input, *index, &state, variables);
+        }
+        *index += 1;
+        returns = get_variable_state(&closure.body, side_effects, index, variables, items);
+    });
+    returns
+}
+
+#[derive(Debug, Clone)]
+struct DeclaredItemFn<'a> {
+    /// The name of the function, for reporting
+    name: String,
+    declaration: DeclareMacro<'a>,
+}
+
+/// find all functions that are marked `declare`
+fn get_all_declared_fns(ast: &syn::File) -> HashMap<String, DeclaredItemFn<'_>> {
+    ItemFnVisitor::collect_from_ast(ast)
+        .item_fns
+        .into_iter()
+        .flat_map(|f| {
+            parse_declaration(&f.attrs).map(|declaration| {
+                (f.sig.ident.to_string(), DeclaredItemFn {
+                    name: f.sig.ident.to_string(),
+                    declaration,
+                })
+            })
+        })
+        .collect()
+}
+
+// The state of the value of `expression`, adding its side effects to `side_effects`
+fn get_variable_state<'a>(
+    expression: &'a syn::Expr,
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    items: &'a CrateItems<'a>,
+) -> VariableState {
+    let first_side_effect = side_effects.len();
+    let state = evaluate_expression(expression, side_effects, index, variables, items);
+    // A call adds its callee to the call path of the effects in it. The innermost expression around
+    // the call is where it happens, ie: the method of `cmd.output()` or the whole of `read(p)`
+    let site = match expression {
+        syn::Expr::MethodCall(method_call) => Location::of(&method_call.method),
+        expression => Location::of(expression),
+    };
+    for side_effect in side_effects[first_side_effect..].iter_mut() {
+        if side_effect.call_sites.len() < side_effect.call_path.len() {
+            side_effect.call_sites.insert(0, site.clone());
+        }
+    }
+    state
+}
+
+fn evaluate_expression<'a>(
+    expression: &'a syn::Expr,
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    items: &'a CrateItems<'a>,
+) -> VariableState {
+    // println!("tokens: {tokens}");
+    match expression {
+        // x in `let y = x;`
+        syn::Expr::Path(ref path) => {
+            // println!("PATH: {expression:?}");
+            let var_name = &path.path.segments[0].ident;
+            match find_variable_metadata(var_name, *index, variables) {
+                Some(var) => var.variable_state.clone(),
+                // fn_with_effects in `apply(fn_with_effects, "foo")`
+                None if items.item_fns.contains_key(var_name) => {
+                    VariableState::callable(Callable::FnItem(var_name.clone()))
+                }
+                // Config in `let config = Config;`
+                None if items.methods.is_type(var_name) => {
+                    VariableState::hole(HoleOrigin::Untracked).with_type(ValueType::Concrete(var_name.to_string()))
+                }
+                // A constant, a static or a variable bound by syntax we skipped
+                None => VariableState::hole(items.unsupported(format!("path `{}`", path_to_string(&path.path)), path)),
+            }
+        }
+        // |p| std::fs::read(p) in `let f = |p| std::fs::read(p);`
+        syn::Expr::Closure(ref closure) => {
+            let inputs = closure.inputs.iter().cloned().collect();
+            VariableState::callable(Callable::Closure(capture(inputs, &closure.body, *index, variables)))
+        }
+        // async { .. } in `let fut = async { .. };`, which does nothing until it is awaited or spawned
+        syn::Expr::Async(ref async_block) => {
+            let body = syn::Expr::Block(syn::ExprBlock {
+                attrs: Vec::new(),
+                label: None,
+                block: async_block.block.clone(),
+            });
+            VariableState::callable(Callable::Future(capture(Vec::new(), &body, *index, variables)))
+        }
+        // fut.await in `let y = fut.await;`
+        syn::Expr::Await(ref await_expr) => {
+            let future = get_variable_state(&await_expr.base, side_effects, index, variables, items);
+            match future.callable {
+                Some(ref future @ Callable::Future(_)) => call_callable(future, side_effects, Vec::new(), items).returns,
+                // `async fn` calls are evaluated like any other call, so this is already the output
+                _ => future,
+            }
+        }
+        // std::env::args() in `let args = std::env::args();`, values from outside of the program
+        syn::Expr::Call(ref call) if source_origin(call).is_some() => {
+            let arg_states: Vec<_> = call.args.iter()
+                .map(|arg| get_variable_state(arg, side_effects, index, variables, items))
+                .collect();
+            match source_origin(call).unwrap() {
+                // std::env::var("HOME"), which is a `reads_env` effect. Any variable could be read by `vars()`
+                HoleOrigin::EnvVar(_) => {
+                    let name = arg_states.into_iter().next().unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked));
+                    let exact_name = match name.alternatives.as_slice() {
+                        [alternative] => alternative.exact(),
+                        _ => None,
+                    };
+                    push_source_effect(call, "reads_env", name, side_effects, items);
+                    match exact_name.as_ref().and_then(|name| items.assumed_env.get(name)) {
+                        Some(value) => VariableState::value(value.clone()).tainted_by(HoleOrigin::EnvVar(exact_name)),
+                        None => VariableState::hole(HoleOrigin::EnvVar(exact_name)),
+                    }
+                }
+                // std::fs::read_to_string(p), which is a `reads_file` effect and returns what's in the file
+                HoleOrigin::FileRead => {
+                    let path = arg_states.into_iter().next().unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked));
+                    push_source_effect(call, "reads_file", path, side_effects, items);
+                    VariableState::hole(HoleOrigin::FileRead)
+                }
+                origin => VariableState::hole(origin),
+            }
+        }
+        // TcpStream::connect(addr) in `let stream = TcpStream::connect(addr)?;`, std and tokio functions
+        // with network effects. Whatever they return is read from the network
+        syn::Expr::Call(ref call) if network::declaration(&call.func).is_some() => {
+            let declared_fn = network::declaration(&call.func).unwrap();
+            let arg_states = call.args.iter()
+                .map(|arg| get_variable_state(arg, side_effects, index, variables, items))
+                .collect();
+            let first_side_effect = side_effects.len();
+            items.call(&declared_fn.name, CallKind::Declared);
+            evaluate_declared_fn(side_effects, &declared_fn, arg_states);
+            within_call(&mut side_effects[first_side_effect..], &declared_fn.name);
+            VariableState::hole(HoleOrigin::NetworkRead)
+        }
+        // Command::new("git") in `Command::new("git").arg("status").output()`
+        syn::Expr::Call(ref call) if process::is_command_new(&call.func) && call.args.len() == 1 => {
+            let program = get_variable_state(&call.args[0], side_effects, index, variables, items);
+            VariableState::command(ProcessCommand::new(program))
+        }
+        // tokio::spawn(async move { .. }) or std::thread::spawn(move || { .. })
+        syn::Expr::Call(ref call) if is_spawn(&call.func, items) => {
+            for arg in call.args.iter() {
+                let task = get_variable_state(arg, side_effects, index, variables, items);
+                if let Some(ref task) = task.callable {
+                    call_callable(task, side_effects, Vec::new(), items);
+                }
+            }
+            // The join handle
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // "foo" in `let y = "foo";`
+        syn::Expr::Lit(ref lit) => {
+            // println!("LIT: {expression:?}");
+            match lit.lit {
+                syn::Lit::Str(ref s) => VariableState::value(s.value()),
+                // Unsuffixed integers are `i32` unless inferred otherwise, which doesn't matter to us
+                syn::Lit::Int(ref i) => match i.base10_parse() {
+                    Ok(value) => VariableState::integer(value, if i.suffix().is_empty() { "i32" } else { i.suffix() }),
+                    Err(_) => VariableState::hole(items.unsupported("literal", lit)),
+                },
+                syn::Lit::Bool(ref b) => VariableState::value(b.value.to_string()).with_type(ValueType::Concrete("bool".to_string())),
+                _ => VariableState::hole(items.unsupported("literal", lit)),
+            }
+        }
+        // Config { .. } in `let config = Config { path: "foo" };`
+        syn::Expr::Struct(ref expr_struct) => {
+            for field in expr_struct.fields.iter() {
+                get_variable_state(&field.expr, side_effects, index, variables, items);
+            }
+            match expr_struct.path.segments.last() {
+                Some(segment) => VariableState::hole(HoleOrigin::Untracked).with_type(ValueType::Concrete(segment.ident.to_string())),
+                None => VariableState::hole(HoleOrigin::Untracked),
+            }
+        }
+        // Ipv4Addr::new(127, 0, 0, 1), written the way it's parsed: `127.0.0.1`
+        syn::Expr::Call(ref call) if is_ip_addr_new(&call.func) => {
+            let octets: Vec<_> = call.args.iter()
+                .map(|arg| get_variable_state(arg, side_effects, index, variables, items))
+                .collect();
+            VariableState::joined(&octets, ".")
+        }
+        // SocketAddr::new(ip, port) or SocketAddr::from(([0, 0, 0, 0], 8080)), which we track as the
+        // `(ip, port)` tuple they're made from
+        syn::Expr::Call(ref call) if is_socket_addr_new(&call.func) => {
+            let mut fields = Vec::new();
+            for arg in call.args.iter() {
+                match arg {
+                    syn::Expr::Tuple(tuple) if call.args.len() == 1 => {
+                        for elem in tuple.elems.iter() {
+                            fields.push(match elem {
+                                syn::Expr::Array(octets) => {
+                                    let octets: Vec<_> = octets.elems.iter()
+                                        .map(|octet| get_variable_state(octet, side_effects, index, variables, items))
+                                        .collect();
+                                    VariableState::joined(&octets, ".")
+                                }
+                                _ => get_variable_state(elem, side_effects, index, variables, items),
+                            });
+                        }
+                    }
+                    _ => fields.push(get_variable_state(arg, side_effects, index, variables, items)),
+                }
+            }
+            match fields.len() {
+                1 => fields.remove(0),
+                _ => VariableState::tuple(fields),
+            }
+        }
+        // Box::new(x) in `let y = Box::new(x);`, smart pointers are transparent to us
+        syn::Expr::Call(ref call) if is_smart_pointer_new(&call.func) && call.args.len() == 1 => {
+            get_variable_state(&call.args[0], side_effects, index, variables, items)
+        }
+        // Config::new("foo") in `let config = Config::new("foo");`
+        syn::Expr::Call(ref call) if associated_fn(&call.func, items).is_some() => {
+            let (self_ty, fn_name) = associated_fn(&call.func, items).unwrap();
+            let mut arg_states = Vec::new();
+            for arg in call.args.iter() {
+                arg_states.push(get_variable_state(arg, side_effects, index, variables, items));
+            }
+            let self_ty = self_ty.to_string();
+            match items.methods.resolve(&self_ty, fn_name) {
+                Some(method) => {
+                    let evaluated = call_method(method, &self_ty, fn_name, side_effects, arg_states, items);
+                    for (arg_i, state) in evaluated.mutated_arguments {
+                        if let Some(var_name) = call.args.iter().nth(arg_i).and_then(mutated_variable) {
+                            assign_variable(var_name, *index, state, variables);
+                        }
+                    }
+                    evaluated.returns
+                }
+                None => {
+                    let callee = format!("{}::{}", self_ty, fn_name);
+                    if items.is_ignored(&callee) {
+                        return VariableState::hole(HoleOrigin::Ignored(callee));
+                    }
+                    items.call(&callee, CallKind::Unresolved);
+                    items.gap(GapKind::UnresolvedCallee(callee.clone()), &call.func);
+                    VariableState::hole(HoleOrigin::UnresolvedCallee(callee))
+                }
+            }
+        }
+        // foo("bar") in `let y = foo("bar");`
+        syn::Expr::Call(ref call) => {
+            let path = match call.func.as_ref() {
+                syn::Expr::Path(ref path) => &path.path,
+                // fns[0](p) or (get_handler())(p)
+                _ => return VariableState::hole(items.unsupported("call of an expression", &call.func)),
+            };
+            let fn_name = &path.segments[0].ident;
+
+            // println!("Evaluating fn_name: {fn_name}");
+
+            // Given a call we need to calculate the return value
+            // based on its inputs
+            let mut arg_states = Vec::new();
+            for arg in call.args.iter() {
+                arg_states.push(get_variable_state(arg, side_effects, index, variables, items));
+            }
+
+            // `f(p)` where `f` is a variable holding a closure or a function, rather than a function itself
+            let callable = find_variable_metadata(fn_name, *index, variables)
+                .and_then(|var| var.variable_state.callable.clone());
+            let evaluated = match callable {
+                Some(callable) => call_callable(&callable, side_effects, arg_states, items),
+                None if path.segments.len() == 1 && items.item_fns.contains_key(fn_name) => {
+                    call_fn_item(fn_name, side_effects, arg_states, items)
+                }
+                // Most likely a function from another crate, which we know nothing about
+                None => {
+                    let callee = path_to_string(path);
+                    if items.is_ignored(&callee) {
+                        return VariableState::hole(HoleOrigin::Ignored(callee));
+                    }
+                    let local = ["crate", "self", "super", "Self"].iter().any(|root| path.segments[0].ident == root);
+                    let kind = match path.segments.len() {
+                        1 if items.foreign_fns.contains(fn_name) => GapKind::Ffi(callee.clone()),
+                        1 => GapKind::UnresolvedCallee(callee.clone()),
+                        _ if local => GapKind::UnresolvedCallee(callee.clone()),
+                        _ => GapKind::UndeclaredExternal(callee.clone()),
+                    };
+                    match kind {
+                        GapKind::UnresolvedCallee(_) => items.call(&callee, CallKind::Unresolved),
+                        _ => items.call(&callee, CallKind::External),
+                    }
+                    items.gap(kind, &call.func);
+                    return VariableState::hole(HoleOrigin::UnresolvedCallee(callee));
+                }
+            };
+
+            // Arguments passed as `&mut x` now hold whatever the callee left in them
+            for (arg_i, state) in evaluated.mutated_arguments {
+                if let Some(var_name) = call.args.iter().nth(arg_i).and_then(mutated_variable) {
+                    assign_variable(var_name, *index, state, variables);
+                }
+            }
+            // Now we have to calculate the state of the return value
+            evaluated.returns
+        }
+        // x.to_string() in `let y = x.to_string();`
+        syn::Expr::MethodCall(ref method_call)
+            if ["to_string", "to_owned", "clone", "into", "unwrap", "expect"].iter().any(|m| method_call.method == m) =>
+        {
+            get_variable_state(&method_call.receiver, side_effects, index, variables, items)
+        }
+        // p.push_str(".json") in `p.push_str(".json");`
+        syn::Expr::MethodCall(ref method_call) if method_call.method == "push_str" => {
+            let suffix = get_variable_state(&method_call.args[0], side_effects, index, variables, items);
+            if let Some(var_name) = mutated_variable(&method_call.receiver) {
+                if let Some(var) = find_variable_metadata(var_name, *index, variables) {
+                    let mut state = var.variable_state.clone();
+                    state.concat(&suffix);
+                    assign_variable(var_name, *index, state, variables);
+                }
+            }
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // *p = "foo" in `*p = "foo";`
+        syn::Expr::Assign(ref assign) => {
+            let state = get_variable_state(&assign.right, side_effects, index, variables, items);
+            if let Some(var_name) = mutated_variable(&assign.left) {
+                assign_variable(var_name, *index, state, variables);
+            }
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // *p += ".json" in `*p += ".json";`
+        syn::Expr::AssignOp(ref assign_op) if matches!(assign_op.op, syn::BinOp::AddEq(_)) => {
+            let suffix = get_variable_state(&assign_op.right, side_effects, index, variables, items);
+            if let Some(var_name) = mutated_variable(&assign_op.left) {
+                if let Some(var) = find_variable_metadata(var_name, *index, variables) {
+                    let mut state = var.variable_state.clone();
+                    state.concat(&suffix);
+                    assign_variable(var_name, *index, state, variables);
+                }
+            }
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // paths.iter().map(|p| std::fs::read(p)) in `let contents = paths.iter().map(|p| std::fs::read(p));`
+        // Collections are tracked as the state of any one of their items
+        syn::Expr::MethodCall(ref method_call)
+            if ITERATOR_METHODS.iter().any(|m| method_call.method == m) =>
+        {
+            let elements = get_variable_state(&method_call.receiver, side_effects, index, variables, items);
+            let mut closure_returns = VariableState::hole(HoleOrigin::Untracked);
+            for arg in method_call.args.iter() {
+                match arg {
+                    syn::Expr::Closure(closure) => {
+                        closure_returns = evaluate_closure(closure, &elements, side_effects, index, variables, items);
+                    }
+                    _ => {
+                        let arg_state = get_variable_state(arg, side_effects, index, variables, items);
+                        // paths.iter().map(read) or paths.iter().map(f) where `f` is a closure
+                        if let Some(callable) = arg_state.callable {
+                            evaluate_repeated(side_effects, index, variables, |side_effects, _, _| {
+                                let evaluated = call_callable(&callable, side_effects, vec![elements.clone()], items);
+                                closure_returns = evaluated.returns;
+                            });
+                        }
+                    }
+                }
+            }
+            match method_call.method.to_string().as_str() {
+                "map" | "filter_map" | "flat_map" | "map_while" | "find_map" => closure_returns,
+                "iter" | "iter_mut" | "into_iter" | "filter" | "inspect" | "find" | "take_while"
+                | "skip_while" | "collect" | "cloned" | "copied" => elements,
+                _ => VariableState::hole(HoleOrigin::Untracked),
+            }
+        }
+        // ("0.0.0.0", 8080) in `TcpListener::bind(("0.0.0.0", 8080))`
+        syn::Expr::Tuple(ref tuple) => {
+            let fields = tuple.elems.iter()
+                .map(|elem| get_variable_state(elem, side_effects, index, variables, items))
+                .collect();
+            VariableState::tuple(fields)
+        }
+        // addr.1 in `let port = addr.1;`
+        syn::Expr::Field(syn::ExprField { ref base, member: syn::Member::Unnamed(ref member), .. }) => {
+            get_variable_state(base, side_effects, index, variables, items).field(member.index as usize)
+        }
+        // port + 1 in `let port = port + 1;`, or a string concatenation when neither side is an integer
+        syn::Expr::Binary(ref binary) => {
+            let lhs = get_variable_state(&binary.left, side_effects, index, variables, items);
+            let rhs = get_variable_state(&binary.right, side_effects, index, variables, items);
+            match binary.op {
+                syn::BinOp::Add(_) if !lhs.is_integer() && !rhs.is_integer() => {
+                    let mut concatenated = lhs;
+                    concatenated.concat(&rhs);
+                    concatenated
+                }
+                syn::BinOp::Add(_) => lhs.arithmetic(&rhs, i128::checked_add),
+                syn::BinOp::Sub(_) => lhs.arithmetic(&rhs, i128::checked_sub),
+                syn::BinOp::Mul(_) => lhs.arithmetic(&rhs, i128::checked_mul),
+                syn::BinOp::Div(_) => lhs.arithmetic(&rhs, i128::checked_div),
+                syn::BinOp::Rem(_) => lhs.arithmetic(&rhs, i128::checked_rem),
+                _ => VariableState::hole(items.unsupported("binary operator", &binary.op)),
+            }
+        }
+        // -1 in `let offset = -1;`
+        syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), ref expr, .. }) => {
+            let operand = get_variable_state(expr, side_effects, index, variables, items);
+            VariableState::integer(0, "i32").arithmetic(&operand, i128::checked_sub)
+        }
+        // ["a", "b"] in `for p in ["a", "b"] { .. }`, the state of any one of its elements
+        syn::Expr::Array(ref array) => {
+            let mut elements = array.elems.iter()
+                .map(|elem| get_variable_state(elem, side_effects, index, variables, items));
+            let first = elements.next().unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked));
+            elements.fold(first, |joined, element| joined.join(&element))
+        }
+        syn::Expr::Reference(
+            syn::ExprReference {expr, ..}
+        ) => {
+            get_variable_state(expr.as_ref(), side_effects, index, variables, items)
+        }
+        // read(p)? in `let contents = read(p)?;`
+        syn::Expr::Try(syn::ExprTry { expr, .. }) | syn::Expr::Paren(syn::ExprParen { expr, .. }) => {
+            get_variable_state(expr.as_ref(), side_effects, index, variables, items)
+        }
+        // args[1] in `let path = args[1];`, the state of any one of the elements
+        syn::Expr::Index(ref index_expr) => {
+            get_variable_state(&index_expr.index, side_effects, index, variables, items);
+            get_variable_state(&index_expr.expr, side_effects, index, variables, items)
+        }
+        // if verbose { .. } else { .. }, where the condition is evaluated again for each branch, so
+        // that `if let` bindings and anything it assigns are seen by both
+        syn::Expr::If(ref if_expr) => {
+            evaluate_branches(2, side_effects, index, variables, |branch, side_effects, index, variables| {
+                match branch {
+                    0 => {
+                        get_variable_state(&if_expr.cond, side_effects, index, variables, items);
+                        evaluate_block(&if_expr.then_branch.stmts, side_effects, index, variables, items)
+                            .unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked))
+                    }
+                    _ => {
+                        get_variable_state(&if_expr.cond, &mut Vec::new(), index, variables, items);
+                        match if_expr.else_branch {
+                            Some((_, ref else_branch)) => get_variable_state(else_branch, side_effects, index, variables, items),
+                            None => VariableState::hole(HoleOrigin::Untracked),
+                        }
+                    }
+                }
+            })
+        }
+        // match mode { Mode::Read => .., Mode::Write(p) => .. }
+        syn::Expr::Match(ref match_expr) => {
+            let scrutinee = get_variable_state(&match_expr.expr, side_effects, index, variables, items);
+            evaluate_branches(match_expr.arms.len(), side_effects, index, variables, |arm, side_effects, index, variables| {
+                let arm = &match_expr.arms[arm];
+                *index += 1;
+                bind_pattern(&arm.pat, *index, &scrutinee, variables);
+                if let Some((_, ref guard)) = arm.guard {
+                    get_variable_state(guard, side_effects, index, variables, items);
+                }
+                get_variable_state(&arm.body, side_effects, index, variables, items)
+            })
+        }
+        // for p in paths { .. }
+        syn::Expr::ForLoop(ref for_loop) => {
+            let elements = get_variable_state(&for_loop.expr, side_effects, index, variables, items);
+            evaluate_repeated(side_effects, index, variables, |side_effects, index, variables| {
+                *index += 1;
+                bind_pattern(&for_loop.pat, *index, &elements, variables);
+                evaluate_block(&for_loop.body.stmts, side_effects, index, variables, items);
+            });
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // while running { .. }
+        syn::Expr::While(ref while_loop) => {
+            evaluate_repeated(side_effects, index, variables, |side_effects, index, variables| {
+                get_variable_state(&while_loop.cond, side_effects, index, variables, items);
+                evaluate_block(&while_loop.body.stmts, side_effects, index, variables, items);
+            });
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // loop { .. }
+        syn::Expr::Loop(ref loop_expr) => {
+            evaluate_repeated(side_effects, index, variables, |side_effects, index, variables| {
+                evaluate_block(&loop_expr.body.stmts, side_effects, index, variables, items);
+            });
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // let Some(p) = next() in `while let Some(p) = next() { .. }`
+        syn::Expr::Let(ref let_expr) => {
+            let state = get_variable_state(&let_expr.expr, side_effects, index, variables, items);
+            *index += 1;
+            bind_pattern(&let_expr.pat, *index, &state, variables);
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        // { .. } in `let y = { .. };`
+        syn::Expr::Block(ref block) => {
+            evaluate_block(&block.block.stmts, side_effects, index, variables, items)
+                .unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked))
+        }
+        // unsafe { .. }, whose body we analyze like any other block
+        syn::Expr::Unsafe(ref unsafe_block) => {
+            items.gap(GapKind::Unsafe, &unsafe_block.unsafe_token);
+            evaluate_block(&unsafe_block.block.stmts, side_effects, index, variables, items)
+                .unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked))
+        }
+        // asm!("syscall", ..)
+        syn::Expr::Macro(ref mac) if ["asm", "global_asm", "llvm_asm"].iter().any(|name| mac.mac.path.is_ident(name)) => {
+            items.gap(GapKind::InlineAsm, &mac.mac.path);
+            VariableState::hole(HoleOrigin::Untracked)
+        }
+        syn::Expr::Break(_) | syn::Expr::Continue(_) => VariableState::hole(HoleOrigin::Untracked),
+        // source.load() in `let config = source.load();`
+        syn::Expr::MethodCall(ref method_call) => {
+            let receiver = get_variable_state(&method_call.receiver, side_effects, index, variables, items);
+            if let Some(ref command) = receiver.command {
+                return evaluate_command_method(method_call, command, side_effects, index, variables, items);
+            }
+            let value_type = receiver.value_type.clone();
+            let unknown_receiver = if receiver.is_hole() { Some(receiver.clone()) } else { None };
+            let mut arg_states = vec![receiver];
+            for arg in method_call.args.iter() {
+                arg_states.push(get_variable_state(arg, side_effects, index, variables, items));
+            }
+
+            // The arguments are kept for the std and tokio methods, in case the crate has no such method
+            let evaluated = match value_type {
+                // Monomorphised, we know exactly which method this is
+                Some(ValueType::Concrete(ref self_ty)) => items.methods.resolve(self_ty, &method_call.method)
+                    .map(|method| call_method(method, self_ty, &method_call.method, side_effects, arg_states.clone(), items)),
+                Some(ValueType::Dyn(ref trait_name)) => {
+                    call_dyn_method(trait_name, &method_call.method, side_effects, arg_states.clone(), items)
+                }
+                None => None,
+            };
+            let evaluated = match (evaluated, unknown_receiver) {
+                (Some(evaluated), _) => evaluated,
+                // socket.send_to(buf, addr) or addr.to_socket_addrs(), std and tokio methods with network effects
+                (None, _) if network::method_declaration(&method_call.method).is_some() => {
+                    let declared_fn = network::method_declaration(&method_call.method).unwrap();
+                    let first_side_effect = side_effects.len();
+                    items.call(&declared_fn.name, CallKind::Declared);
+                    evaluate_declared_fn(side_effects, &declared_fn, arg_states);
+                    within_call(&mut side_effects[first_side_effect..], &declared_fn.name);
+                    return VariableState::hole(HoleOrigin::NetworkRead);
+                }
+                // Nothing is known about the receiver, so whatever comes out of it is just as unknown,
+                // ie: `std::env::args().nth(1)` or `stdin.read_line(&mut line)`
+                (None, Some(receiver)) if READ_METHODS.iter().chain(DERIVING_METHODS).any(|m| method_call.method == m) => {
+                    if READ_METHODS.iter().any(|m| method_call.method == m) {
+                        for var_name in method_call.args.iter().filter_map(mutated_variable) {
+                            assign_variable(var_name, *index, receiver.clone(), variables);
+                        }
+                    }
+                    return receiver;
+                }
+                // Any other method could do anything, ie: `db.drop_everything()` on what an undeclared
+                // function returned. What it returns is still as untrusted as the receiver
+                (None, Some(receiver)) => {
+                    let callee = method_call.method.to_string();
+                    items.call(&callee, CallKind::Unresolved);
+                    items.gap(GapKind::UnresolvedCallee(callee.clone()), &method_call.method);
+                    let mut returns = VariableState::hole(HoleOrigin::UnresolvedCallee(callee));
+                    returns.add_taints(&receiver.taints);
+                    return returns;
+                }
+                (None, None) => {
+                    let syntax = format!("method call `{}`", method_call.method);
+                    return VariableState::hole(items.unsupported(syntax, &method_call.method));
+                }
+            };
+
+            // The receiver is argument 0
+            for (arg_i, state) in evaluated.mutated_arguments {
+                let arg = match arg_i {
+                    0 => Some(method_call.receiver.as_ref()),
+                    _ => method_call.args.iter().nth(arg_i - 1),
+                };
+                if let Some(var_name) = arg.and_then(mutated_variable) {
+                    assign_variable(var_name, *index, state, variables);
+                }
+            }
+            evaluated.returns
+        }
+        _ => VariableState::hole(items.unsupported("expression", expression)),
+    }
+}
+
+// cmd.arg("-l") in `cmd.arg("-l").output()`. Builder methods change the command they're called on,
+// and running it is an `exec` effect
+fn evaluate_command_method<'a>(
+    method_call: &'a syn::ExprMethodCall,
+    command: &ProcessCommand,
+    side_effects: &mut Vec<SideEffect>,
+    index: &mut u16,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+    items: &'a CrateItems<'a>,
+) -> VariableState {
+    let mut command = command.clone();
+    let method = method_call.method.to_string();
+    for arg in method_call.args.iter() {
+        // Every element of an array literal is its own argument, anything else is a single one
+        if let (Some(array), "args") = (array_literal(arg), method.as_str()) {
+            for elem in array.elems.iter() {
+                command.args.push(get_variable_state(elem, side_effects, index, variables, items));
+            }
+            continue;
+        }
+        let state = get_variable_state(arg, side_effects, index, variables, items);
+        match method.as_str() {
+            "arg" | "args" => command.args.push(state),
+            "current_dir" => command.current_dir = Some(state),
+            // `env`, `stdin` and the like don't change what runs
+            _ => {}
+        }
+    }
+
+    if process::RUN_METHODS.contains(&method.as_str()) {
+        let callee = format!("Command::{}", method);
+        items.call(&callee, CallKind::Declared);
+        side_effects.push(command.exec());
+        let last = side_effects.len() - 1;
+        within_call(&mut side_effects[last..], &callee);
+        return VariableState::hole(HoleOrigin::Untracked);
+    }
+
+    // Builder methods return `&mut Command`, so `cmd.arg("a").arg("b");` changes `cmd`
+    let state = VariableState::command(command);
+    let mut root = method_call.receiver.as_ref();
+    while let syn::Expr::MethodCall(inner) = root {
+        root = inner.receiver.as_ref();
+    }
+    if let Some(var_name) = mutated_variable(root) {
+        assign_variable(var_name, *index, state.clone(), variables);
+    }
+    state
+}
+
+// ["-l", "-a"] in `cmd.args(&["-l", "-a"])`
+fn array_literal(expression: &syn::Expr) -> Option<&syn::ExprArray> {
+    match expression {
+        syn::Expr::Array(array) => Some(array),
+        syn::Expr::Reference(syn::ExprReference { expr, .. }) => array_literal(expr),
+        _ => None,
+    }
+}
+
+// The variable behind an expression that can be written through, ie: `p` in `&mut p` or `*p`
+fn mutated_variable(expression: &syn::Expr) -> Option<&syn::Ident> {
+    match expression {
+        syn::Expr::Path(path) => path.path.get_ident(),
+        syn::Expr::Reference(syn::ExprReference { expr, .. }) => mutated_variable(expr),
+        syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Deref(_), expr, .. }) => mutated_variable(expr),
+        syn::Expr::Paren(syn::ExprParen { expr, .. }) => mutated_variable(expr),
+        _ => None,
+    }
+}
+
+// Record a new state for a variable that already exists, as of statement `index`
+fn assign_variable<'a>(
+    var_name: &'a syn::Ident,
+    index: u16,
+    variable_state: VariableState,
+    variables: &mut BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+) {
+    let mut variable_state = variable_state;
+    variable_state.flows_through(format!("`{}`", var_name));
+    // The new state still belongs to the binding being assigned to, not to one shadowing it later
+    let instance = find_variable_metadata(var_name, index, variables).map_or(index, |var| var.variable_instance_id);
+    variables.insert((index, var_name), VariableMetadata::new(var_name, instance, variable_state));
+}
+
+// Capture the latest state of every variable in scope for a closure or async block
+fn capture<'a>(
+    inputs: Vec<syn::Pat>,
+    body: &syn::Expr,
+    index: u16,
+    variables: &BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>,
+) -> Rc<CapturedClosure> {
+    let mut captured = BTreeMap::new();
+    for ((var_id, var_name), var) in variables.iter() {
+        if *var_id <= index {
+            captured.insert(*var_name, var.variable_state.clone());
+        }
+    }
+    Rc::new(CapturedClosure {
+        inputs,
+        body: body.clone(),
+        captured: captured.into_iter().map(|(var_name, state)| (var_name.clone(), state)).collect(),
+    })
+}
+
+// `spawn`, `spawn_blocking` and `spawn_local` from tokio, async-std or std::thread, which run their argument
+fn is_spawn(func: &syn::Expr, items: &CrateItems<'_>) -> bool {
+    let path = match func {
+        syn::Expr::Path(path) => &path.path,
+        _ => return false,
+    };
+    // A function in the crate that happens to be called `spawn` is just a function
+    if path.get_ident().map(|ident| items.item_fns.contains_key(ident)).unwrap_or(false) {
+        return false;
+    }
+    path.segments.last()
+        .map(|segment| SPAWN_FNS.iter().any(|spawn| segment.ident == spawn))
+        .unwrap_or(false)
+}
+
+// Where the value returned by a call comes from, when it comes from outside of the program.
+// Calls are recognised by the last two segments of their path, ie: `env::args` in `std::env::args()`
+fn source_origin(call: &syn::ExprCall) -> Option<HoleOrigin> {
+    let path = match call.func.as_ref() {
+        syn::Expr::Path(path) => &path.path,
+        _ => return None,
+    };
+    let mut segments = path.segments.iter().rev().map(|segment| segment.ident.to_string());
+    let fn_name = segments.next()?;
+    let module = segments.next()?;
+    match (module.as_str(), fn_name.as_str()) {
+        ("env", "args" | "args_os") => Some(HoleOrigin::Argv),
+        // Named by the value of the argument, which the caller knows
+        ("env", "var" | "var_os" | "vars" | "vars_os") => Some(HoleOrigin::EnvVar(None)),
+        ("io", "stdin") => Some(HoleOrigin::Stdin),
+        ("fs", "read" | "read_to_string") => Some(HoleOrigin::FileRead),
+        _ => None,
+    }
+}
+
+// The side effect of a call that is also a source, ie: `reads_env(name)` for `std::env::var(name)`,
+// called through the std function
+fn push_source_effect(
+    call: &syn::ExprCall,
+    name: &str,
+    argument: VariableState,
+    side_effects: &mut Vec<SideEffect>,
+    items: &CrateItems<'_>,
+) {
+    side_effects.push(SideEffect {
+        name: name.to_string(),
+        arguments: vec![argument],
+        repeated: false,
+        imprecise: false,
+        call_path: Vec::new(),
+        call_sites: Vec::new(),
+        derivations: Vec::new(),
+    });
+    let last = side_effects.len() - 1;
+    if let syn::Expr::Path(ref path) = *call.func {
+        let callee = path_to_string(&path.path);
+        items.call(&callee, CallKind::Declared);
+        within_call(&mut side_effects[last..], &callee);
+    }
+}
+
+// `std::env::args` for the path of `std::env::args()`
+fn path_to_string(path: &syn::Path) -> String {
+    path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<_>>().join("::")
+}
+
+// Box::new, Rc::new and Arc::new
+fn is_smart_pointer_new(func: &syn::Expr) -> bool {
+    match func {
+        syn::Expr::Path(path) => {
+            let segments: Vec<_> = path.path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+            matches!(segments.as_slice(), [ty, new] if ["Box", "Rc", "Arc"].contains(&ty.as_str()) && new == "new")
+        }
+        _ => false,
+    }
+}
+
+// Ipv4Addr::new(127, 0, 0, 1)
+fn is_ip_addr_new(func: &syn::Expr) -> bool {
+    match func {
+        syn::Expr::Path(path) => {
+            let segments: Vec<_> = path.path.segments.iter().rev().take(2).map(|segment| segment.ident.to_string()).collect();
+            matches!(segments.as_slice(), [new, ty] if ty == "Ipv4Addr" && new == "new")
+        }
+        _ => false,
+    }
+}
+
+// SocketAddr::new(ip, port), SocketAddrV4::new(ip, port) or SocketAddr::from((ip, port))
+fn is_socket_addr_new(func: &syn::Expr) -> bool {
+    match func {
+        syn::Expr::Path(path) => {
+            let segments: Vec<_> = path.path.segments.iter().rev().take(2).map(|segment| segment.ident.to_string()).collect();
+            matches!(segments.as_slice(), [new, ty] if ["SocketAddr", "SocketAddrV4"].contains(&ty.as_str()) && ["new", "from"].contains(&new.as_str()))
+        }
+        _ => false,
+    }
+}
+
+// The type and function of a call to an associated function of a type in the crate, ie: `Config::new`
+fn associated_fn<'e>(func: &'e syn::Expr, items: &CrateItems<'_>) -> Option<(&'e Ident, &'e Ident)> {
+    let path = match func {
+        syn::Expr::Path(path) => &path.path,
+        _ => return None,
+    };
+    let mut segments = path.segments.iter().rev();
+    let fn_name = &segments.next()?.ident;
+    let self_ty = &segments.next()?.ident;
+    if items.methods.is_type(self_ty) {
+        Some((self_ty, fn_name))
+    } else {
+        None
+    }
+}
+
+// Call a function by name, using its declaration if it has one and inferring it otherwise
+fn call_fn_item<'a>(
+    fn_name: &syn::Ident,
+    side_effects: &mut Vec<SideEffect>,
+    arg_states: Vec<VariableState>,
+    items: &'a CrateItems<'a>,
+) -> EvaluatedFn {
+    if items.is_ignored(&fn_name.to_string()) {
+        return EvaluatedFn {
+            returns: VariableState::hole(HoleOrigin::Ignored(fn_name.to_string())),
+            mutated_arguments: BTreeMap::new(),
+        };
+    }
+    let item_fn = match items.item_fns.get(fn_name) {
+        Some(item_fn) => item_fn,
+        None => return EvaluatedFn {
+            returns: VariableState::hole(HoleOrigin::UnresolvedCallee(fn_name.to_string())),
+            mutated_arguments: BTreeMap::new(),
+        },
+    };
+    let first_side_effect = side_effects.len();
+    let evaluated = match items.declared_fns.get(&fn_name.to_string()) {
+        Some(f) => {
+            items.call(&fn_name.to_string(), CallKind::Declared);
+            let mut evaluated = evaluate_declared_fn(side_effects, f, arg_states);
+            evaluated.returns.value_type = return_type_of(&item_fn.sig);
+            evaluated
+        },
+        None => {
+            // We must infer this function's declaration
+            items.call(&fn_name.to_string(), CallKind::Inferred);
+            if items.is_recursive(&fn_name.to_string(), fn_name) {
+                return EvaluatedFn {
+                    returns: VariableState::hole(HoleOrigin::Recursion(fn_name.to_string())),
+                    mutated_arguments: BTreeMap::new(),
+                };
+            }
+            items.within(fn_name.to_string(), || {
+                infer_fn(side_effects, &item_fn.sig, &item_fn.block, None, &arg_states, items)
+            })
+        }
+    };
+    within_call(&mut side_effects[first_side_effect..], &fn_name.to_string());
+    evaluated
+}
+
+// Record that `side_effects` happened inside a call to `callee`
+fn within_call(side_effects: &mut [SideEffect], callee: &str) {
+    for side_effect in side_effects {
+        side_effect.call_path.insert(0, callee.to_string());
+    }
+}
+
+// Call a method or associated function, where `arg_states` starts with the receiver for methods.
+// `call_site` is the name of the method where it's called
+fn call_method<'a>(
+    method: ResolvedMethod<'a, 'a>,
+    self_ty: &str,
+    call_site: &Ident,
+    side_effects: &mut Vec<SideEffect>,
+    mut arg_states: Vec<VariableState>,
+    items: &'a CrateItems<'a>,
+) -> EvaluatedFn {
+    let first_side_effect = side_effects.len();
+    let method_name = match method {
+        ResolvedMethod::Declared(declared_fn) => &declared_fn.name,
+        ResolvedMethod::Inferred(method_fn) => &method_fn.sig.ident.to_string(),
+    };
+    let callee = format!("{}::{}", self_ty, method_name);
+    if items.is_ignored(&callee) {
+        return EvaluatedFn {
+            returns: VariableState::hole(HoleOrigin::Ignored(callee)),
+            mutated_arguments: BTreeMap::new(),
+        };
+    }
+    let evaluated = match method {
+        ResolvedMethod::Declared(declared_fn) => {
+            items.call(&callee, CallKind::Declared);
+            // `self` can be left out of `args`, in which case the receiver isn't passed along
+            let has_receiver = declared_fn.declaration.args.args.first()
+                .map(|arg| arg.arg_name == "self")
+                .unwrap_or(false);
+            let skip_receiver = !has_receiver && arg_states.len() > declared_fn.declaration.args.args.len();
+            if skip_receiver {
+                arg_states.remove(0);
+            }
+            let mut evaluated = evaluate_declared_fn(side_effects, declared_fn, arg_states);
+            if skip_receiver {
+                evaluated.mutated_arguments = evaluated.mutated_arguments.into_iter()
+                    .map(|(arg_i, state)| (arg_i + 1, state))
+                    .collect();
+            }
+            evaluated
+        }
+        ResolvedMethod::Inferred(method_fn) => {
+            let block = method_fn.block.expect("only methods with a body are inferred");
+            items.call(&callee, CallKind::Inferred);
+            if items.is_recursive(&callee, call_site) {
+                return EvaluatedFn {
+                    returns: VariableState::hole(HoleOrigin::Recursion(callee)),
+                    mutated_arguments: BTreeMap::new(),
+                };
+            }
+            let mut evaluated = items.within(callee.clone(), || {
+                infer_fn(side_effects, method_fn.sig, block, method_fn.self_ident.as_ref(), &arg_states, items)
+            });
+            // `fn new() -> Self`
+            if evaluated.returns.value_type == Some(ValueType::Concrete("Self".to_string())) {
+                evaluated.returns.value_type = Some(ValueType::Concrete(self_ty.to_string()));
+            }
+            evaluated
+        }
+    };
+    within_call(&mut side_effects[first_side_effect..], &callee);
+    evaluated
+}
+
+// Call a method on a value whose type we only know implements `trait_name`. Unless the trait method
+// is declared, the effects of every implementation in the crate are included and marked imprecise
+fn call_dyn_method<'a>(
+    trait_name: &str,
+    method_name: &Ident,
+    side_effects: &mut Vec<SideEffect>,
+    arg_states: Vec<VariableState>,
+    items: &'a CrateItems<'a>,
+) -> Option<EvaluatedFn> {
+    if let Some(methods::MethodFn { declaration: Some(ref declared_fn), .. }) = items.methods.trait_method(trait_name, method_name) {
+        return Some(call_method(ResolvedMethod::Declared(declared_fn), trait_name, method_name, side_effects, arg_states, items));
+    }
+
+    let mut returns: Option<VariableState> = None;
+    for self_ty in items.methods.implementors(trait_name) {
+        let method = match items.methods.resolve(self_ty, method_name) {
+            Some(method) => method,
+            None => continue,
+        };
+        let mut implementor_side_effects = Vec::new();
+        let mut implementor_arg_states = arg_states.clone();
+        if let Some(receiver) = implementor_arg_states.first_mut() {
+            receiver.value_type = Some(ValueType::Concrete(self_ty.to_string()));
+        }
+        let evaluated = call_method(method, self_ty, method_name, &mut implementor_side_effects, implementor_arg_states, items);
+        side_effects.extend(implementor_side_effects.into_iter().map(|side_effect| SideEffect {
+            imprecise: true,
+            ..side_effect
+        }));
+        returns = Some(match returns {
+            Some(returns) => returns.join(&evaluated.returns),
+            None => evaluated.returns,
+        });
+    }
+
+    // Mutations of the receiver or arguments can't be trusted across implementations
+    returns.map(|returns| EvaluatedFn {
+        returns,
+        mutated_arguments: BTreeMap::new(),
+    })
+}
+
+// Call a function or closure that was passed around as a value
+fn call_callable<'a>(
+    callable: &Callable,
+    side_effects: &mut Vec<SideEffect>,
+    arg_states: Vec<VariableState>,
+    items: &'a CrateItems<'a>,
+) -> EvaluatedFn {
+    let (captured_closure, callee) = match callable {
+        Callable::FnItem(fn_name) => {
+            return call_fn_item(fn_name, side_effects, arg_states, items);
+        }
+        Callable::Closure(captured_closure) => (captured_closure, "<closure>"),
+        Callable::Future(captured_closure) => (captured_closure, "<async block>"),
+    };
+
+    // The closure body sees what it captured, shadowed by its own parameters
+    let mut variables = BTreeMap::new();
+    for (var_name, state) in captured_closure.captured.iter() {
+        variables.insert((0, var_name), VariableMetadata::new(var_name, 0, state.clone()));
+    }
+    for (i, input) in captured_closure.inputs.iter().enumerate() {
+        let state = arg_states.get(i).cloned().unwrap_or_else(|| VariableState::hole(HoleOrigin::Untracked));
+        bind_pattern(input, 1, &state, &mut variables);
+    }
+
+    let mut index = 2;
+    let first_side_effect = side_effects.len();
+    items.call(callee, CallKind::Inferred);
+    let returns = items.within(callee, || get_variable_state(
+        &captured_closure.body,
+        side_effects,
+        &mut index,
+        &mut variables, items,
+    ));
+    within_call(&mut side_effects[first_side_effect..], callee);
+
+    // Mutating captured variables isn't tracked
+    EvaluatedFn {
+        returns,
+        mutated_arguments: BTreeMap::new(),
+    }
+}
+
+fn evaluate_declared_fn(
+    side_effects: &mut Vec<SideEffect>,
+    declared_fn: &DeclaredItemFn<'_>,
+    arguments: Vec<VariableState>
+) -> EvaluatedFn {
+    let declaration = &declared_fn.declaration;
+    // An argument declared with a pattern, ie: `id as I: /[0-9]+/`, is known to match it, which
+    // is more than we know about a value with a hole in it
+    let arguments: Vec<_> = declaration.args.args.iter().zip(arguments).map(|(arg, state)| {
+        let mut state = match arg.pattern {
+            Some(ref pattern) if state.has_hole() => {
+                let narrowed = VariableState::from_pattern(pattern, &state.hole_origins());
+                VariableState { value_type: state.value_type, taints: state.taints, ..narrowed }
+            }
+            _ => state,
+        };
+        state.flows_through(format!("`{}` in `{}`", arg.arg_name, declared_fn.name));
+        state
+    }).collect();
+    let resolved_arguments: HashMap<_, _> = declaration.args.args.iter().zip(arguments.iter()).flat_map(|(arg, state)| {
+        [(arg.arg_binding, state), (arg.arg_name.as_ref(), state)]
+    }).collect();
+
+    let declared_side_effect_stmts = declaration.side_effects.clone().unwrap_or_default().side_effect_stmts;
+    for effect in declared_side_effect_stmts {
+        let mut side_effect = SideEffect {
+            name: effect.side_effect_name.to_string(),
+            arguments: Vec::new(),
+            repeated: false,
+            imprecise: false,
+            call_path: Vec::new(),
+            call_sites: Vec::new(),
+            derivations: Vec::new(),
+        };
+        for arg in effect.side_effect_arguments {
+            side_effect.arguments.push(evaluate_expr(&arg, &resolved_arguments));
+            side_effect.derivations.push(Some(Derivation::of(&arg, &resolved_arguments)));
+        }
+        side_effects.push(side_effect);
+    }
+
+    let mut mutated_arguments = BTreeMap::new();
+    for mutation in declaration.mutates.iter().flat_map(|mutates| &mutates.mutations) {
+        // The `declare` macro rejects mutations of arguments it doesn't have
+        let arg_i = declaration.args.args.iter()
+            .position(|arg| arg.arg_name == mutation.arg_name || arg.arg_binding == mutation.arg_name);
+        if let Some(arg_i) = arg_i {
+            mutated_arguments.insert(arg_i, evaluate_expr(&mutation.expr, &resolved_arguments));
+        }
+    }
+
+    let returns = match declaration.returns {
+        Some(ref returns) => evaluate_expr(returns, &resolved_arguments),
+        // If there are no returns, then we can't know anything about the return value
+        None => VariableState::hole(HoleOrigin::UndeclaredReturn(declared_fn.name.clone())),
+    };
+
+    EvaluatedFn {
+        returns,
+        mutated_arguments,
+    }
+}
+
+fn evaluate_expr(expr: &Expr, arguments: &HashMap<&str, &VariableState>) -> VariableState {
+    match expr {
+        Expr::LitStr(s) => VariableState::value(s.value.to_string()),
+        Expr::LitInt(i) => VariableState::integer(i.value, "i32"),
+        Expr::LitBool(b) => VariableState::value(b.value.to_string()).with_type(ValueType::Concrete("bool".to_string())),
+        // A variable the declaration doesn't bind could be anything
+        Expr::Var(v) => arguments.get(v.name)
+            .map(|state| (*state).clone())
+            .unwrap_or_else(|| VariableState::hole(HoleOrigin::Parameter(v.name.to_string()))),
+        // Integers are added, anything else is concatenated
+        Expr::Add(add) => {
+            let lhs = evaluate_expr(&add.lhs, arguments);
+            let rhs = evaluate_expr(&add.rhs, arguments);
+            if lhs.is_integer() || rhs.is_integer() {
+                return lhs.arithmetic(&rhs, i128::checked_add);
+            }
+            let mut concatenated = lhs;
+            concatenated.concat(&rhs);
+            concatenated.value_type = None;
+            concatenated
+        }
+        Expr::Arithmetic(arithmetic) => {
+            let lhs = evaluate_expr(&arithmetic.lhs, arguments);
+            let rhs = evaluate_expr(&arithmetic.rhs, arguments);
+            match arithmetic.op {
+                ArithmeticOp::Sub => lhs.arithmetic(&rhs, i128::checked_sub),
+                ArithmeticOp::Mul => lhs.arithmetic(&rhs, i128::checked_mul),
+            }
+        }
+        Expr::Union(union) => evaluate_expr(&union.lhs, arguments).join(&evaluate_expr(&union.rhs, arguments)),
+        Expr::Tuple(fields) => VariableState::tuple(fields.iter().map(|field| evaluate_expr(field, arguments)).collect()),
+    }
+}
+
+fn find_variable_metadata<'a, 'b>(
+    find_var_name: &syn::Ident,
+    last_before: u16,
+    variables: &'b BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>
+) -> Option<&'b VariableMetadata<'a>> {
+    for ((var_id, var_name), var) in variables.iter().rev() {
+        if find_var_name == *var_name && *var_id <= last_before {
+            return Some(var);
+        }
+    }
+    None
+}
+
+// The latest state of the binding of `find_var_name` declared at `instance`, after any assignments
+// to it but ignoring any later `let` shadowing it
+fn find_binding<'a, 'b>(
+    find_var_name: &syn::Ident,
+    instance: u16,
+    variables: &'b BTreeMap<(u16, &'a syn::Ident), VariableMetadata<'a>>
+) -> Option<&'b VariableMetadata<'a>> {
+    variables.iter().rev()
+        .find(|((_, var_name), var)| find_var_name == *var_name && var.variable_instance_id == instance)
+        .map(|(_, var)| var)
+}
+
+// extract the identifiers from the let binding
+fn extract_variables_from_pat(pat: &syn::Pat) -> Vec<&syn::Ident> {
+    match pat {
+        syn::Pat::Ident(ref ident) => vec![&ident.ident],
+        syn::Pat::Tuple(ref tuple) => tuple
+            .elems
+            .iter()
+            .flat_map(extract_variables_from_pat)
+            .collect(),
+        syn::Pat::Reference(ref reference) => extract_variables_from_pat(&reference.pat),
+        syn::Pat::Type(ref pat_type) => extract_variables_from_pat(&pat_type.pat),
+        syn::Pat::Box(ref pat_box) => extract_variables_from_pat(&pat_box.pat),
+        // Some(x) in `if let Some(x) = opt`
+        syn::Pat::TupleStruct(ref tuple_struct) => tuple_struct.pat.elems.iter().flat_map(extract_variables_from_pat).collect(),
+        syn::Pat::Struct(ref pat_struct) => pat_struct.fields.iter().flat_map(|field| extract_variables_from_pat(&field.pat)).collect(),
+        syn::Pat::Slice(ref slice) => slice.elems.iter().flat_map(extract_variables_from_pat).collect(),
+        // Every case of an or-pattern binds the same variables
+        syn::Pat::Or(ref or) => or.cases.first().map(extract_variables_from_pat).unwrap_or_default(),
+        // Literals, ranges, paths, `_` and `..` don't bind anything
+        _ => vec![],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum VariableStateConstraint {
+    Hole(HoleOrigin),
+    /// Unknown characters from a known alphabet, ie: `[0-9]+`, with the origins of any holes it
+    /// narrows, ie: an argument from `std::env::args()` declared as `/[0-9]+/`
+    Class(CharClass, Vec<HoleOrigin>),
+    Value(String),
+}
+
+/// Where the unknown part of a value came from, so a broad policy can be traced back to its cause
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HoleOrigin {
+    /// `std::env::args()`
+    Argv,
+    /// `std::env::var("HOME")`, the name is `None` when it isn't a literal
+    EnvVar(Option<String>),
+    /// `std::io::stdin()`
+    Stdin,
+    /// A network connection, or anything read from one
+    NetworkRead,
+    /// The contents of a file, ie: `std::fs::read_to_string(p)`
+    FileRead,
+    /// A parameter that wasn't given a value, ie: the parameters of the entrypoint
+    Parameter(String),
+    /// Syntax the analysis doesn't support yet, at a line and column of the analyzed file
+    Unsupported { syntax: String, line: usize, column: usize },
+    /// A call to a function we can't find, ie: one from another crate
+    UnresolvedCallee(String),
+    /// A declared function without `returns`
+    UndeclaredReturn(String),
+    /// A call to a function the analysis was told to ignore
+    Ignored(String),
+    /// A recursive call, which isn't followed again
+    Recursion(String),
+    /// A value we don't track the contents of, ie: a struct, a destructured tuple or `()`
+    Untracked,
+    /// Values that differed between loop iterations or trait implementations were joined,
+    /// along with the origins of any holes they had
+    Widened(Vec<HoleOrigin>),
+}
+
+impl HoleOrigin {
+    fn unsupported(syntax: impl Into<String>, node: &impl syn::spanned::Spanned) -> Self {
+        let start = node.span().start();
+        HoleOrigin::Unsupported {
+            syntax: syntax.into(),
+            line: start.line,
+            column: start.column + 1,
+        }
+    }
+
+    /// A single origin for a hole made of `origins`, flattening widened ones
+    fn widened(origins: impl IntoIterator<Item = HoleOrigin>) -> Self {
+        let mut flattened = Vec::new();
+        for origin in origins {
+            for leaf in origin.leaves() {
+                if !flattened.contains(leaf) {
+                    flattened.push(leaf.clone());
+                }
+            }
+        }
+        match flattened.len() {
+            1 => flattened.remove(0),
+            _ => HoleOrigin::Widened(flattened),
+        }
+    }
+
+    /// The origins behind this one, looking through `Widened`. A join of known values is its own leaf
+    fn leaves(&self) -> Vec<&HoleOrigin> {
+        match self {
+            HoleOrigin::Widened(origins) if !origins.is_empty() => origins.iter().flat_map(HoleOrigin::leaves).collect(),
+            origin => vec![origin],
+        }
+    }
+}
+
+impl Display for HoleOrigin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HoleOrigin::Argv => write!(f, "command line arguments"),
+            HoleOrigin::EnvVar(Some(name)) => write!(f, "environment variable {}", name),
+            HoleOrigin::EnvVar(None) => write!(f, "an environment variable"),
+            HoleOrigin::Stdin => write!(f, "standard input"),
+            HoleOrigin::NetworkRead => write!(f, "a network read"),
+            HoleOrigin::FileRead => write!(f, "the contents of a file"),
+            HoleOrigin::Parameter(name) => write!(f, "parameter `{}`", name),
+            HoleOrigin::Unsupported { syntax, line, column } => write!(f, "unsupported {} at {}:{}", syntax, line, column),
+            HoleOrigin::UnresolvedCallee(name) => write!(f, "unresolved call to `{}`", name),
+            HoleOrigin::Ignored(name) => write!(f, "ignored call to `{}`", name),
+            HoleOrigin::Recursion(name) => write!(f, "recursive call to `{}`", name),
+            HoleOrigin::UndeclaredReturn(name) => write!(f, "`{}` declares no returns", name),
+            HoleOrigin::Untracked => write!(f, "an untracked value"),
+            HoleOrigin::Widened(origins) if origins.is_empty() => write!(f, "values joined across loop iterations or implementations"),
+            HoleOrigin::Widened(origins) => {
+                write!(f, "any of ")?;
+                for (i, origin) in origins.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", origin)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+
+/// What we know about the type of a value, used to resolve method calls
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ValueType {
+    /// A known type, ie: `Config`
+    Concrete(String),
+    /// Some type implementing a trait, ie: `dyn Read`, `impl Read` or `T: Read`
+    Dyn(String),
+}
+
+// The type of values of `ty` as far as the syntax tells us. Generic parameters are looked up in `generics`
+fn value_type_of(ty: &syn::Type, generics: &syn::Generics) -> Option<ValueType> {
+    match ty {
+        syn::Type::Reference(reference) => value_type_of(&reference.elem, generics),
+        syn::Type::Paren(paren) => value_type_of(&paren.elem, generics),
+        syn::Type::ImplTrait(impl_trait) => trait_bound(impl_trait.bounds.iter()).map(ValueType::Dyn),
+        syn::Type::TraitObject(trait_object) => trait_bound(trait_object.bounds.iter()).map(ValueType::Dyn),
+        syn::Type::Path(path) => {
+            let segment = path.path.segments.last()?;
+            if ["Box", "Rc", "Arc"].iter().any(|pointer| segment.ident == pointer) {
+                if let syn::PathArguments::AngleBracketed(ref arguments) = segment.arguments {
+                    if let Some(syn::GenericArgument::Type(ref inner)) = arguments.args.first() {
+                        return value_type_of(inner, generics);
+                    }
+                }
+            }
+
+            // `T` in `fn foo<T: Read>(r: T)` or `fn foo<T>(r: T) where T: Read`
+            if generics.type_params().any(|param| param.ident == segment.ident) {
+                let param_bounds = generics.type_params()
+                    .filter(|param| param.ident == segment.ident)
+                    .flat_map(|param| param.bounds.iter());
+                let where_bounds = generics.where_clause.iter()
+                    .flat_map(|where_clause| where_clause.predicates.iter())
+                    .filter_map(|predicate| match predicate {
+                        syn::WherePredicate::Type(predicate) => Some(predicate),
+                        _ => None,
+                    })
+                    .filter(|predicate| matches!(&predicate.bounded_ty, syn::Type::Path(bounded) if bounded.path.is_ident(&segment.ident)))
+                    .flat_map(|predicate| predicate.bounds.iter());
+                return trait_bound(param_bounds.chain(where_bounds)).map(ValueType::Dyn);
+            }
+            Some(ValueType::Concrete(segment.ident.to_string()))
+        }
+        _ => None,
+    }
+}
+
+// The type of the values returned by a function
+fn return_type_of(sig: &syn::Signature) -> Option<ValueType> {
+    match sig.output {
+        syn::ReturnType::Type(_, ref ty) => value_type_of(ty, &sig.generics),
+        syn::ReturnType::Default => None,
+    }
+}
+
+// The name of the first trait in a list of bounds, ie: `Read` in `impl Read + Send`
+fn trait_bound<'b>(mut bounds: impl Iterator<Item = &'b syn::TypeParamBound>) -> Option<String> {
+    bounds.find_map(|bound| match bound {
+        syn::TypeParamBound::Trait(trait_bound) => trait_bound.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    })
+}
+
+/// Something that can be called, held by a variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Callable {
+    /// A function referenced by name, ie: `fn_with_effects` in `apply(fn_with_effects, "foo")`
+    FnItem(syn::Ident),
+    /// A closure, ie: `|p| std::fs::read(p)`
+    Closure(Rc<CapturedClosure>),
+    /// An async block, which runs when it is awaited or spawned, ie: `async move { read(p).await }`
+    Future(Rc<CapturedClosure>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CapturedClosure {
+    /// The closure's parameters, async blocks have none
+    inputs: Vec<syn::Pat>,
+    body: syn::Expr,
+    /// The state of every variable in scope where the closure was created
+    captured: Vec<(syn::Ident, VariableState)>,
+}
+
+/// Unions with more alternatives than this are joined into a single alternative
+const MAX_ALTERNATIVES: usize = 8;
+
+/// What is known about a value, ie: an argument of a side effect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableState {
+    /// Every value the variable could hold, ie: `/etc/app.toml` or `~/.app.toml`. Usually just one
+    alternatives: Vec<Alternative>,
+    /// Set when the variable holds a closure or a function
+    callable: Option<Callable>,
+    /// The type of the value, when we know it
+    value_type: Option<ValueType>,
+    /// The fields of a tuple, ie: the host and port of `("0.0.0.0", 8080)`. Empty for anything else
+    fields: Vec<VariableState>,
+    /// Set when the variable holds a `std::process::Command`
+    command: Option<Box<ProcessCommand>>,
+    /// The untrusted values that flowed into this one, whether or not they're still holes
+    taints: Vec<Taint>,
+}
+
+impl VariableState {
+    fn empty() -> Self {
+        Self::from_alternative(Alternative::default())
+    }
+
+    fn value(value: String) -> Self {
+        Self::from_alternative(Alternative {
+            constraints: vec![VariableStateConstraint::Value(value)],
+        })
+    }
+
+    fn hole(origin: HoleOrigin) -> Self {
+        Self {
+            taints: Taint::of(&origin).into_iter().collect(),
+            ..Self::from_alternative(Alternative {
+                constraints: vec![VariableStateConstraint::Hole(origin)],
+            })
+        }
+    }
+
+    fn callable(callable: Callable) -> Self {
+        Self {
+            callable: Some(callable),
+            ..Self::hole(HoleOrigin::Untracked)
+        }
+    }
+
+    fn from_alternative(alternative: Alternative) -> Self {
+        Self {
+            alternatives: vec![alternative],
+            callable: None,
+            value_type: None,
+            fields: Vec::new(),
+            command: None,
+            taints: Vec::new(),
+        }
+    }
+
+    fn command(command: ProcessCommand) -> Self {
+        Self {
+            command: Some(Box::new(command)),
+            ..Self::hole(HoleOrigin::Untracked)
+        }
+    }
+
+    fn integer(value: i128, ty: &str) -> Self {
+        Self::value(value.to_string()).with_type(ValueType::Concrete(ty.to_string()))
+    }
+
+    /// A tuple, which as a string is its fields joined with `:`, the way `("0.0.0.0", 8080)` is
+    /// written as the socket address `0.0.0.0:8080`
+    fn tuple(fields: Vec<VariableState>) -> Self {
+        if fields.is_empty() {
+            return Self::hole(HoleOrigin::Untracked);
+        }
+        Self {
+            fields: fields.clone(),
+            ..Self::joined(&fields, ":")
+        }
+    }
+
+    // The concatenation of `states` with `separator` between each of them
+    fn joined(states: &[VariableState], separator: &str) -> Self {
+        let mut joined = Self::empty();
+        for (i, state) in states.iter().enumerate() {
+            if i > 0 {
+                joined.concat(&Self::value(separator.to_string()));
+            }
+            joined.concat(state);
+        }
+        joined
+    }
+
+    /// The state of the `index`th field of a tuple, ie: `addr.1`
+    fn field(&self, index: usize) -> VariableState {
+        self.fields.get(index).cloned().unwrap_or_else(|| Self::hole(HoleOrigin::Untracked))
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(self.value_type, Some(ValueType::Concrete(ref ty)) if INTEGER_TYPES.contains(&ty.as_str()))
+    }
+
+    // Every value of an integer that is known exactly, ie: `80 | 443`
+    fn integers(&self) -> Option<Vec<i128>> {
+        if !self.is_integer() {
+            return None;
+        }
+        self.alternatives.iter().map(|alternative| match alternative.constraints.as_slice() {
+            [VariableStateConstraint::Value(value)] => value.parse().ok(),
+            _ => None,
+        }).collect()
+    }
+
+    /// `op` applied to every pair of values of `self` and `rhs`, ie: `8000 + 80`. The result is a
+    /// hole when either side isn't an integer we know exactly, or when `op` overflows
+    fn arithmetic(&self, rhs: &VariableState, op: fn(i128, i128) -> Option<i128>) -> VariableState {
+        let value_type = if self.is_integer() { &self.value_type } else { &rhs.value_type };
+        let values = self.integers().zip(rhs.integers()).and_then(|(lhs, rhs)| {
+            lhs.iter()
+                .flat_map(|l| rhs.iter().map(move |r| op(*l, *r)))
+                .collect::<Option<Vec<_>>>()
+        });
+        let mut state = match values {
+            Some(values) => values.into_iter()
+                .map(|value| Self::value(value.to_string()))
+                .reduce(|joined, value| joined.join(&value))
+                .unwrap_or_else(|| Self::hole(HoleOrigin::Untracked)),
+            None => {
+                let origins: Vec<_> = self.hole_origins().into_iter().chain(rhs.hole_origins()).cloned().collect();
+                match origins.is_empty() {
+                    true => Self::hole(HoleOrigin::Untracked),
+                    false => Self::hole(HoleOrigin::widened(origins)),
+                }
+            }
+        };
+        state.add_taints(&self.taints);
+        state.add_taints(&rhs.taints);
+        Self {
+            value_type: value_type.clone(),
+            ..state
+        }
+    }
+
+    fn with_type(self, value_type: ValueType) -> Self {
+        Self {
+            value_type: Some(value_type),
+            ..self
+        }
+    }
+
+    /// A state made of what `pattern` matches, ie: `/[0-9]+/`, narrowing a value whose holes came
+    /// from `origins`
+    fn from_pattern(pattern: &Pattern, origins: &[&HoleOrigin]) -> Self {
+        let origins: Vec<_> = origins.iter().map(|origin| (*origin).clone()).collect();
+        let mut alternative = Alternative::default();
+        for atom in pattern.atoms.iter() {
+            match (&atom.class, atom.max) {
+                (PatternClass::Literal(c), Some(max)) if max == atom.min => {
+                    for _ in 0..max {
+                        alternative.push_piece(Piece::Char(*c));
+                    }
+                }
+                _ => alternative.push_piece(Piece::Class(CharClass::from_atom(atom), origins.clone())),
+            }
+        }
+        Self::from_alternative(alternative)
+    }
+
+    /// A state that is either `self` or `other`, ie: `/etc/app.toml` joined with `~/.app.toml`.
+    /// Alternatives already covered by another are dropped, and past `MAX_ALTERNATIVES` they are
+    /// all joined into one
+    fn join(&self, other: &VariableState) -> VariableState {
+        if self == other {
+            return self.clone();
+        }
+
+        let mut joined = VariableState {
+            alternatives: Vec::new(),
+            callable: None,
+            value_type: None,
+            fields: Vec::new(),
+            command: None,
+            taints: self.taints.clone(),
+        };
+        joined.add_taints(&other.taints);
+        for alternative in self.alternatives.iter().chain(other.alternatives.iter()) {
+            joined.add_alternative(alternative.clone());
+        }
+        if joined.alternatives.len() > MAX_ALTERNATIVES {
+            joined.collapse();
+        }
+        if self.callable == other.callable {
+            joined.callable = self.callable.clone();
+        }
+        if self.value_type == other.value_type {
+            joined.value_type = self.value_type.clone();
+        }
+        if self.fields.len() == other.fields.len() {
+            joined.fields = self.fields.iter().zip(other.fields.iter()).map(|(l, r)| l.join(r)).collect();
+        }
+        if let (Some(lhs), Some(rhs)) = (&self.command, &other.command) {
+            joined.command = lhs.join(rhs).map(Box::new);
+        }
+        joined
+    }
+
+    /// Like `join`, but a union that grew is joined into a single alternative, and classes that
+    /// grew lose their upper bound, so that values growing on every iteration of a loop converge.
+    /// ie: `~x` widened with `~xx` is `~x[x]*` rather than `~x` or `~xx`
+    fn widen(&self, other: &VariableState) -> VariableState {
+        let mut widened = self.join(other);
+        if self.fields.len() == other.fields.len() {
+            widened.fields = self.fields.iter().zip(other.fields.iter()).map(|(l, r)| l.widen(r)).collect();
+        }
+        if widened.alternatives.len() > self.alternatives.len() {
+            widened.collapse();
+        }
+        let known_classes: Vec<_> = self.alternatives.iter()
+            .flat_map(|alternative| alternative.constraints.iter())
+            .collect();
+        for constraint in widened.alternatives.iter_mut().flat_map(|alternative| alternative.constraints.iter_mut()) {
+            if let VariableStateConstraint::Class(class, origins) = constraint {
+                if !known_classes.contains(&&VariableStateConstraint::Class(class.clone(), origins.clone())) {
+                    class.max_len = None;
+                }
+            }
+        }
+        widened
+    }
+
+    // Add an alternative unless another one already covers it, dropping the ones it covers
+    fn add_alternative(&mut self, alternative: Alternative) {
+        if self.alternatives.iter().any(|existing| existing.covers(&alternative)) {
+            return;
+        }
+        self.alternatives.retain(|existing| !alternative.covers(existing));
+        self.alternatives.push(alternative);
+    }
+
+    // Join every alternative into one
+    fn collapse(&mut self) {
+        let mut alternatives = std::mem::take(&mut self.alternatives).into_iter();
+        let first = alternatives.next().unwrap_or_default();
+        self.alternatives = vec![alternatives.fold(first, |joined, alternative| joined.join(&alternative))];
+    }
+
+    /// Append `suffix` to every alternative, ie: `p.push_str(".json")`
+    fn concat(&mut self, suffix: &VariableState) {
+        let mut concatenated = Vec::new();
+        for alternative in self.alternatives.iter() {
+            for suffix in suffix.alternatives.iter() {
+                let mut alternative = alternative.clone();
+                for piece in suffix.pieces() {
+                    alternative.push_piece(piece);
+                }
+                if !concatenated.contains(&alternative) {
+                    concatenated.push(alternative);
+                }
+            }
+        }
+        self.alternatives = concatenated;
+        if self.alternatives.len() > MAX_ALTERNATIVES {
+            self.collapse();
+        }
+        self.add_taints(&suffix.taints);
+    }
+
+    /// The same value, coming from `origin` when it's a source, ie: a variable given `--assume-env`
+    fn tainted_by(mut self, origin: HoleOrigin) -> Self {
+        self.add_taints(&Taint::of(&origin).into_iter().collect::<Vec<_>>());
+        self
+    }
+
+    // Add the taints of another state flowing into this one. A source that already taints this one
+    // keeps the flow it came through, so that loops converge
+    fn add_taints(&mut self, taints: &[Taint]) {
+        for taint in taints {
+            if !self.taints.iter().any(|known| known.origin == taint.origin) {
+                self.taints.push(taint.clone());
+            }
+        }
+    }
+
+    /// Record that the value flowed through `step`, ie: a variable or a parameter
+    fn flows_through(&mut self, step: impl Into<String>) {
+        let step = step.into();
+        for taint in self.taints.iter_mut() {
+            if !taint.flow.contains(&step) {
+                taint.flow.push(step.clone());
+            }
+        }
+    }
+
+    /// The untrusted values in this one, even where they are known exactly or narrowed to a pattern
+    pub fn taints(&self) -> &[Taint] {
+        &self.taints
+    }
+
+    /// Where every hole in this state came from, including those narrowed to a class
+    pub fn hole_origins(&self) -> Vec<&HoleOrigin> {
+        let mut origins = Vec::new();
+        for constraint in self.alternatives.iter().flat_map(|alternative| alternative.constraints.iter()) {
+            let leaves = match constraint {
+                VariableStateConstraint::Hole(origin) => origin.leaves(),
+                VariableStateConstraint::Class(_, class_origins) => class_origins.iter().flat_map(HoleOrigin::leaves).collect(),
+                VariableStateConstraint::Value(_) => continue,
+            };
+            for leaf in leaves {
+                if !origins.contains(&leaf) {
+                    origins.push(leaf);
+                }
+            }
+        }
+        origins
+    }
+
+    /// Every alternative as a glob, ie: `["/etc/app.toml", "~/*.toml"]`, with `*`, `?`, `[` and `\`
+    /// escaped where they're part of a known value
+    pub fn globs(&self) -> Vec<String> {
+        self.alternatives.iter().map(Alternative::escaped_glob).collect()
+    }
+
+    /// Whether nothing at all is known about the value, ie: a line read from stdin
+    fn is_hole(&self) -> bool {
+        matches!(self.alternatives.as_slice(), [alternative] if matches!(alternative.constraints.as_slice(), [VariableStateConstraint::Hole(_)]))
+    }
+
+    /// Whether any part of the value could be anything
+    fn has_hole(&self) -> bool {
+        self.alternatives.iter()
+            .flat_map(|alternative| alternative.constraints.iter())
+            .any(|constraint| matches!(constraint, VariableStateConstraint::Hole(_)))
+    }
+}
+
+/// One of the values a variable could hold, as a sequence of constraints
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Alternative {
+    constraints: Vec<VariableStateConstraint>,
+}
+
+impl Alternative {
+    /// An alternative that covers both `self` and `other`, keeping whatever prefix and suffix they
+    /// share. The rest becomes a class of the characters that differ, ie: `~/a.json` joined with
+    /// `~/b.json` is `~/[ab].json`, or a hole if either of them had a hole there
+    fn join(&self, other: &Alternative) -> Alternative {
+        if self == other {
+            return self.clone();
+        }
+
+        let lhs = self.pieces();
+        let rhs = other.pieces();
+        let prefix = lhs.iter().zip(&rhs).take_while(|(l, r)| l == r).count();
+        let suffix = lhs[prefix..].iter().rev()
+            .zip(rhs[prefix..].iter().rev())
+            .take_while(|(l, r)| l == r)
+            .count();
+
+        let mut joined = Alternative::default();
+        for piece in lhs[..prefix].iter().cloned() {
+            joined.push_piece(piece);
+        }
+        if let Some(middle) = cover(&lhs[prefix..lhs.len() - suffix], &rhs[prefix..rhs.len() - suffix]) {
+            joined.push_piece(middle);
+        }
+        for piece in lhs[lhs.len() - suffix..].iter().cloned() {
+            joined.push_piece(piece);
+        }
+        joined
+    }
+
+    // Whether every value of `other` is a value of `self`, as far as `join` can tell
+    fn covers(&self, other: &Alternative) -> bool {
+        self.join(other) == *self
+    }
+
+    // The alternative as a sequence of characters, classes and holes
+    fn pieces(&self) -> Vec<Piece> {
+        self.constraints.iter().flat_map(|constraint| match constraint {
+            VariableStateConstraint::Hole(origin) => vec![Piece::Hole(origin.clone())],
+            VariableStateConstraint::Class(class, origins) => vec![Piece::Class(class.clone(), origins.clone())],
+            VariableStateConstraint::Value(value) => value.chars().map(Piece::Char).collect(),
+        }).collect()
+    }
+
+    // Append a piece, merging it into the last constraint where possible
+    fn push_piece(&mut self, piece: Piece) {
+        match (self.constraints.last_mut(), piece) {
+            (Some(VariableStateConstraint::Hole(last)), Piece::Hole(origin)) => {
+                if *last != origin {
+                    *last = HoleOrigin::widened([last.clone(), origin]);
+                }
+            }
+            // A hole already matches anything a class next to it could, but it keeps where the class
+            // came from
+            (Some(VariableStateConstraint::Hole(last)), Piece::Class(_, origins)) => {
+                if !origins.is_empty() {
+                    *last = HoleOrigin::widened(std::iter::once(last.clone()).chain(origins));
+                }
+            }
+            (Some(last @ VariableStateConstraint::Class(..)), Piece::Hole(origin)) => {
+                let origin = match last {
+                    VariableStateConstraint::Class(_, origins) if !origins.is_empty() => {
+                        HoleOrigin::widened(origins.drain(..).chain(std::iter::once(origin)))
+                    }
+                    _ => origin,
+                };
+                *last = VariableStateConstraint::Hole(origin);
+            }
+            (Some(VariableStateConstraint::Class(last, last_origins)), Piece::Class(class, origins)) if last.alphabet == class.alphabet => {
+                last.min_len += class.min_len;
+                last.max_len = last.max_len.zip(class.max_len).map(|(l, r)| l + r);
+                add_origins(last_origins, origins);
+            }
+            (Some(VariableStateConstraint::Value(value)), Piece::Char(c)) => value.push(c),
+            (_, Piece::Hole(origin)) => self.constraints.push(VariableStateConstraint::Hole(origin)),
+            (_, Piece::Class(class, origins)) => self.constraints.push(VariableStateConstraint::Class(class, origins)),
+            (_, Piece::Char(c)) => self.constraints.push(VariableStateConstraint::Value(c.to_string())),
+        }
+    }
+
+    /// The value of an alternative that is known exactly
+    fn exact(&self) -> Option<String> {
+        match self.constraints.as_slice() {
+            [] => Some(String::new()),
+            [VariableStateConstraint::Value(value)] => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// The alternative as a glob, ie: `~/*.json`
+    fn glob(&self) -> String {
+        self.glob_with(false)
+    }
+
+    /// The alternative as a glob that matches its known parts literally, ie: `/srv/\*` for the value
+    /// `/srv/*`, so that it doesn't cover a hole when read back from a baseline
+    fn escaped_glob(&self) -> String {
+        self.glob_with(true)
+    }
+
+    fn glob_with(&self, escape: bool) -> String {
+        let mut globholed = String::with_capacity(self.constraints.len());
+
+        for constraint in self.constraints.iter() {
+            match constraint {
+                VariableStateConstraint::Hole(_) => globholed.push('*'),
+                VariableStateConstraint::Class(class, _) => globholed.push_str(&class.glob()),
+                VariableStateConstraint::Value(value) if escape => {
+                    for c in value.chars() {
+                        if ['*', '?', '[', '\\'].contains(&c) {
+                            globholed.push('\\');
+                        }
+                        globholed.push(c);
+                    }
+                }
+                VariableStateConstraint::Value(value) => globholed.push_str(value),
+            }
+        }
+
+        globholed
+    }
+}
+
+/// A single character or unknown part of a state, so that states can be compared piece by piece
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Char(char),
+    Class(CharClass, Vec<HoleOrigin>),
+    Hole(HoleOrigin),
+}
+
+// Add the leaves of `new` to `origins`, skipping those it already has
+fn add_origins(origins: &mut Vec<HoleOrigin>, new: impl IntoIterator<Item = HoleOrigin>) {
+    for origin in new {
+        for leaf in origin.leaves() {
+            if !origins.contains(leaf) {
+                origins.push(leaf.clone());
+            }
+        }
+    }
+}
+
+// A single piece covering both `lhs` and `rhs`. Holes swallow everything, otherwise the characters
+// are combined into a class as long as the longest of the two
+fn cover(lhs: &[Piece], rhs: &[Piece]) -> Option<Piece> {
+    if lhs.is_empty() && rhs.is_empty() {
+        return None;
+    }
+    let mut origins = Vec::new();
+    let mut class_origins = Vec::new();
+    let mut alphabet = Vec::new();
+    for piece in lhs.iter().chain(rhs) {
+        match piece {
+            Piece::Char(c) => alphabet.push((*c, *c)),
+            Piece::Class(class, origins) => {
+                alphabet.extend_from_slice(&class.alphabet);
+                add_origins(&mut class_origins, origins.iter().cloned());
+            }
+            Piece::Hole(origin) => origins.push(origin.clone()),
+        }
+    }
+    if !origins.is_empty() {
+        // The hole remembers where any holes and classes it swallowed came from
+        return Some(Piece::Hole(HoleOrigin::widened(origins.into_iter().chain(class_origins))));
+    }
+
+    let len_bounds = |pieces: &[Piece]| pieces.iter().fold((0, Some(0)), |(min_len, max_len): (usize, Option<usize>), piece| match piece {
+        Piece::Class(class, _) => (min_len + class.min_len, max_len.zip(class.max_len).map(|(l, r)| l + r)),
+        _ => (min_len + 1, max_len.map(|max_len| max_len + 1)),
+    });
+    let (lhs_min, lhs_max) = len_bounds(lhs);
+    let (rhs_min, rhs_max) = len_bounds(rhs);
+    let max_len = lhs_max.zip(rhs_max).map(|(l, r)| l.max(r));
+    Some(Piece::Class(CharClass::new(alphabet, lhs_min.min(rhs_min), max_len), class_origins))
+}
+
+impl Display for VariableState {
+    /// Each alternative as a quoted glob, ie: `"/etc/app.toml" | "~/*.toml"`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, glob) in globholes(self).iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{:?}", glob)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every alternative of a state as a glob, ie: `["/etc/app.toml", "~/*.toml"]`
+fn globholes(state: &VariableState) -> Vec<String> {
+    state.alternatives.iter().map(Alternative::glob).collect()
+}
+
+// Every environment variable the entrypoint can read, `*` for those whose name isn't known
+fn env_vars_read(side_effects: &[SideEffect]) -> Vec<String> {
+    let mut names = Vec::new();
+    for name in side_effects.iter()
+        .filter(|side_effect| side_effect.name == "reads_env")
+        .filter_map(|side_effect| side_effect.arguments.first())
+        .flat_map(globholes)
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[derive(Debug, Clone)]
+struct VariableMetadata<'a> {
+    /// The ident of the variable ie the `x` in `let x = 1;`
+    #[allow(dead_code)]
+    variable_name: Option<&'a syn::Ident>,
+    /// The instance number of this variable, allowing us to differentiate between
+    /// variables with the same name (due to shadowing). Assignments keep the instance they assign to
+    variable_instance_id: u16,
+    /// The known constraints on this variable
+    variable_state: VariableState,
+}
+
+impl<'a> VariableMetadata<'a> {
+    fn new(
+        variable_name: impl Into<Option<&'a syn::Ident>>,
+        variable_instance_id: u16,
+        variable_state: VariableState,
+    ) -> Self {
+        Self {
+            variable_name: variable_name.into(),
+            variable_instance_id,
+            variable_state,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeSet;
+
+use syn::visit::{self, Visit};
+use syn::{Ident, ImplItemMethod, ItemImpl, ItemTrait, TraitItemMethod};
+
+use crate::{parse_declaration, DeclaredItemFn};
+
+/// A method or associated function, ie: `read` in `impl Read for File { fn read(&self) {..} }`
+#[derive(Debug, Clone)]
+pub struct MethodFn<'a> {
+    /// The type the method is implemented on, `None` for the methods of a trait definition
+    pub self_ty: Option<String>,
+    /// The trait being implemented or defined, `None` for inherent methods
+    pub trait_name: Option<String>,
+    pub sig: &'a syn::Signature,
+    /// Trait methods without a default implementation have no body
+    pub block: Option<&'a syn::Block>,
+    pub declaration: Option<DeclaredItemFn<'a>>,
+    /// An ident to bind the receiver to, `self` has no ident of its own in the signature
+    pub self_ident: Option<Ident>,
+}
+
+impl<'a> MethodFn<'a> {
+    fn new(
+        self_ty: Option<String>,
+        trait_name: Option<String>,
+        attrs: &[syn::Attribute],
+        sig: &'a syn::Signature,
+        block: Option<&'a syn::Block>,
+    ) -> Self {
+        let self_ident = sig.receiver().and_then(|receiver| match receiver {
+            syn::FnArg::Receiver(receiver) => Some(Ident::new("self", receiver.self_token.span)),
+            _ => None,
+        });
+        Self {
+            self_ty,
+            trait_name,
+            sig,
+            block,
+            declaration: parse_declaration(attrs).map(|declaration| DeclaredItemFn {
+                name: sig.ident.to_string(),
+                declaration,
+            }),
+            self_ident,
+        }
+    }
+}
+
+/// How a method call on a value of a known type is evaluated
+#[derive(Debug, Clone, Copy)]
+pub enum ResolvedMethod<'m, 'a> {
+    /// The method, or the trait method it implements, has a `declare` macro
+    Declared(&'m DeclaredItemFn<'a>),
+    /// The method must be inferred from its body
+    Inferred(&'m MethodFn<'a>),
+}
+
+/// Every method, trait and type defined in the crate
+#[derive(Debug, Clone, Default)]
+pub struct Methods<'a> {
+    methods: Vec<MethodFn<'a>>,
+    /// Every `(type, trait)` with an `impl Trait for Type`
+    trait_impls: BTreeSet<(String, String)>,
+    /// The names of every struct, enum and type with an `impl` block
+    types: BTreeSet<String>,
+}
+
+impl<'a> Methods<'a> {
+    pub fn collect_from_ast(ast: &'a syn::File) -> Self {
+        let mut methods = Methods::default();
+        methods.visit_file(ast);
+        methods
+    }
+
+    /// Whether `name` is a type defined in the crate, ie: `Config` in `Config::new()`
+    pub fn is_type(&self, name: &Ident) -> bool {
+        self.types.iter().any(|ty| name == ty)
+    }
+
+    /// Find what `self_ty.method_name()` calls. Implementations take priority over the declaration
+    /// of the trait method they implement, which takes priority over a default implementation
+    pub fn resolve(&self, self_ty: &str, method_name: &Ident) -> Option<ResolvedMethod<'_, 'a>> {
+        let implemented = self.methods.iter().find(|method| {
+            method.self_ty.as_deref() == Some(self_ty) && method.sig.ident == *method_name
+        });
+        if let Some(method) = implemented {
+            if let Some(ref declaration) = method.declaration {
+                return Some(ResolvedMethod::Declared(declaration));
+            }
+            let trait_method = method.trait_name.as_deref()
+                .and_then(|trait_name| self.trait_method(trait_name, method_name));
+            if let Some(MethodFn { declaration: Some(ref declaration), .. }) = trait_method {
+                return Some(ResolvedMethod::Declared(declaration));
+            }
+            return Some(ResolvedMethod::Inferred(method));
+        }
+
+        // Default methods of the traits implemented by `self_ty`
+        self.trait_impls.iter()
+            .filter(|(ty, _)| ty == self_ty)
+            .filter_map(|(_, trait_name)| self.trait_method(trait_name, method_name))
+            .find_map(|method| match method.declaration {
+                Some(ref declaration) => Some(ResolvedMethod::Declared(declaration)),
+                None => method.block.map(|_| ResolvedMethod::Inferred(method)),
+            })
+    }
+
+    /// The method of a trait definition, ie: `read` in `trait Read { fn read(&self); }`
+    pub fn trait_method(&self, trait_name: &str, method_name: &Ident) -> Option<&MethodFn<'a>> {
+        self.methods.iter().find(|method| {
+            method.self_ty.is_none()
+                && method.trait_name.as_deref() == Some(trait_name)
+                && method.sig.ident == *method_name
+        })
+    }
+
+    /// Every type in the crate that implements `trait_name`
+    pub fn implementors<'m>(&'m self, trait_name: &'m str) -> impl Iterator<Item = &'m str> + 'm {
+        self.trait_impls.iter()
+            .filter(move |(_, implemented)| implemented == trait_name)
+            .map(|(ty, _)| ty.as_str())
+    }
+}
+
+impl<'a> Visit<'a> for Methods<'a> {
+    fn visit_item_struct(&mut self, node: &'a syn::ItemStruct) {
+        self.types.insert(node.ident.to_string());
+        visit::visit_item_struct(self, node);
+    }
+
+    fn visit_item_enum(&mut self, node: &'a syn::ItemEnum) {
+        self.types.insert(node.ident.to_string());
+        visit::visit_item_enum(self, node);
+    }
+
+    fn visit_item_impl(&mut self, node: &'a ItemImpl) {
+        let self_ty = match type_name(&node.self_ty) {
+            Some(self_ty) => self_ty,
+            None => return,
+        };
+        let trait_name = node.trait_.as_ref()
+            .and_then(|(_, path, _)| path.segments.last())
+            .map(|segment| segment.ident.to_string());
+
+        self.types.insert(self_ty.clone());
+        if let Some(ref trait_name) = trait_name {
+            self.trait_impls.insert((self_ty.clone(), trait_name.clone()));
+        }
+        for item in &node.items {
+            if let syn::ImplItem::Method(ImplItemMethod { attrs, sig, block, .. }) = item {
+                self.methods.push(MethodFn::new(
+                    Some(self_ty.clone()),
+                    trait_name.clone(),
+                    attrs,
+                    sig,
+                    Some(block),
+                ));
+            }
+        }
+        visit::visit_item_impl(self, node);
+    }
+
+    fn visit_item_trait(&mut self, node: &'a ItemTrait) {
+        for item in &node.items {
+            if let syn::TraitItem::Method(TraitItemMethod { attrs, sig, default, .. }) = item {
+                self.methods.push(MethodFn::new(
+                    None,
+                    Some(node.ident.to_string()),
+                    attrs,
+                    sig,
+                    default.as_ref(),
+                ));
+            }
+        }
+        visit::visit_item_trait(self, node);
+    }
+}
+
+// The name of a type as it would be written in an `impl`, ie: `Config` for `crate::Config<T>`
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        syn::Type::Reference(reference) => type_name(&reference.elem),
+        syn::Type::Paren(paren) => type_name(&paren.elem),
+        _ => None,
+    }
+}
@@ -0,0 +1,235 @@
+use std::net::{IpAddr, SocketAddr};
+
+use autobox_effect_parser::ast::DeclareMacro;
+use autobox_effect_parser::registry::{EffectRegistry, SandboxMapping};
+
+use crate::{Alternative, DeclaredItemFn, Piece, SideEffect, VariableState};
+
+/// The effects of std and tokio functions that use the network, by the last two segments of their
+/// path. Addresses are anything `ToSocketAddrs` takes, ie: `("0.0.0.0", 8080)` or `"[::1]:443"`
+const DECLARATIONS: &[(&str, &str, &str)] = &[
+    ("TcpStream", "connect", "args=(addr as A), side_effects=(connect(A))"),
+    ("TcpListener", "bind", "args=(addr as A), side_effects=(bind(A), listen(A))"),
+    ("UdpSocket", "bind", "args=(addr as A), side_effects=(bind(A, 'udp'))"),
+    ("net", "lookup_host", "args=(host as H), side_effects=(resolve(H))"),
+];
+
+/// The effects of std and tokio methods that use the network, by name. The receiver is the first argument
+const METHOD_DECLARATIONS: &[(&str, &str)] = &[
+    ("send_to", "args=(socket as S, buf as B, addr as A), side_effects=(send_to(A))"),
+    ("to_socket_addrs", "args=(addr as A), side_effects=(resolve(A))"),
+];
+
+/// The declaration of a call to a std or tokio function that uses the network, ie: `TcpStream::connect`
+pub fn declaration(func: &syn::Expr) -> Option<DeclaredItemFn<'static>> {
+    let path = match func {
+        syn::Expr::Path(path) => &path.path,
+        _ => return None,
+    };
+    let mut segments = path.segments.iter().rev().map(|segment| segment.ident.to_string());
+    let fn_name = segments.next()?;
+    let ty = segments.next()?;
+    let (_, _, declaration) = DECLARATIONS.iter().find(|(t, f, _)| *t == ty && *f == fn_name)?;
+    Some(parse(format!("{}::{}", ty, fn_name), declaration))
+}
+
+/// The declaration of a call to a std or tokio method that uses the network, ie: `socket.send_to(buf, addr)`
+pub fn method_declaration(method: &syn::Ident) -> Option<DeclaredItemFn<'static>> {
+    let (name, declaration) = METHOD_DECLARATIONS.iter().find(|(name, _)| method == name)?;
+    Some(parse(name.to_string(), declaration))
+}
+
+fn parse(name: String, declaration: &'static str) -> DeclaredItemFn<'static> {
+    let (_, declaration) = DeclareMacro::parse(declaration).unwrap();
+    DeclaredItemFn { name, declaration }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkEffectKind {
+    /// `connect(addr)` or `connect(host, port)`
+    Connect,
+    /// `bind(addr)` or `bind(host, port)`
+    Bind,
+    /// `listen(addr)`, accepting connections on a bound address
+    Listen,
+    /// `resolve(hostname)`, a DNS lookup
+    Resolve,
+    /// `send_to(addr)`, a UDP datagram
+    SendTo,
+}
+
+impl NetworkEffectKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "connect" => Some(Self::Connect),
+            "bind" => Some(Self::Bind),
+            "listen" => Some(Self::Listen),
+            "resolve" => Some(Self::Resolve),
+            "send_to" => Some(Self::SendTo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    // The protocol named by an argument that is exactly `"tcp"` or `"udp"`
+    fn from_state(state: &VariableState) -> Option<Self> {
+        match state.alternatives.as_slice() {
+            [alternative] => match alternative.exact()?.as_str() {
+                "tcp" => Some(Self::Tcp),
+                "udp" => Some(Self::Udp),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The address family of a socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Family {
+    Inet,
+    Inet6,
+}
+
+/// A host and port used by a network effect, each `None` when it isn't known exactly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl Endpoint {
+    /// The address of the host, when it's an IP rather than a hostname
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.host.as_ref()?.parse().ok()
+    }
+
+    // The endpoint of an alternative of an address, split at its last `:`, ie: `[::1]:443`
+    fn from_address(alternative: &Alternative) -> Self {
+        if let Some(value) = alternative.exact() {
+            if let Ok(addr) = value.parse::<SocketAddr>() {
+                return Self { host: Some(addr.ip().to_string()), port: Some(addr.port()) };
+            }
+            if value.parse::<IpAddr>().is_ok() {
+                return Self { host: Some(value), port: None };
+            }
+        }
+        let pieces = alternative.pieces();
+        let (host, port) = match pieces.iter().rposition(|piece| *piece == Piece::Char(':')) {
+            Some(colon) => (&pieces[..colon], Some(&pieces[colon + 1..])),
+            None => (&pieces[..], None),
+        };
+        let host = exact(host).map(|host| host.trim_start_matches('[').trim_end_matches(']').to_string());
+        let port = port.and_then(exact).and_then(|port| port.parse().ok());
+        Self { host, port }
+    }
+}
+
+// The string made of `pieces` when they're all known characters
+fn exact(pieces: &[Piece]) -> Option<String> {
+    pieces.iter().map(|piece| match piece {
+        Piece::Char(c) => Some(*c),
+        _ => None,
+    }).collect()
+}
+
+/// A side effect that uses the network
+#[derive(Debug)]
+pub struct NetworkEffect<'s> {
+    pub kind: NetworkEffectKind,
+    pub protocol: Protocol,
+    /// One per alternative of the address, so that every value it could have gets its own rule
+    pub endpoints: Vec<Endpoint>,
+    pub side_effect: &'s SideEffect,
+}
+
+impl<'s> NetworkEffect<'s> {
+    /// The network effect of a side effect the registry maps to `network(kind)`. Its arguments are
+    /// an address or a host and port, optionally followed by `"tcp"` or `"udp"`
+    pub fn from_side_effect(side_effect: &'s SideEffect, registry: &EffectRegistry) -> Option<Self> {
+        let kind = registry.get(&side_effect.name)?.mappings.iter().find_map(|mapping| match mapping {
+            SandboxMapping::Network(kind) => NetworkEffectKind::from_name(kind),
+            _ => None,
+        })?;
+        let mut arguments = side_effect.arguments.as_slice();
+        let mut protocol = match kind {
+            NetworkEffectKind::Resolve | NetworkEffectKind::SendTo => Protocol::Udp,
+            _ => Protocol::Tcp,
+        };
+        if let [rest @ .., last] = arguments {
+            if let Some(last) = Protocol::from_state(last) {
+                protocol = last;
+                arguments = rest;
+            }
+        }
+
+        let endpoints = match arguments {
+            [] => vec![Endpoint { host: None, port: None }],
+            // A `(host, port)` tuple keeps its fields, so they don't need to be split apart again
+            [addr] if addr.fields.len() == 2 => endpoints(&addr.fields[0], &addr.fields[1]),
+            [addr] => addr.alternatives.iter().map(Endpoint::from_address).collect(),
+            [host, port, ..] => endpoints(host, port),
+        };
+        Some(Self {
+            kind,
+            protocol,
+            endpoints,
+            side_effect,
+        })
+    }
+
+    /// The families of the sockets the effect uses. Hostnames and unknown hosts could resolve to
+    /// either, as could the address of a DNS server
+    pub fn families(&self) -> Vec<Family> {
+        let mut families = Vec::new();
+        for endpoint in self.endpoints.iter() {
+            let endpoint_families: &[Family] = match endpoint.ip() {
+                Some(IpAddr::V4(_)) if self.kind != NetworkEffectKind::Resolve => &[Family::Inet],
+                Some(IpAddr::V6(_)) if self.kind != NetworkEffectKind::Resolve => &[Family::Inet6],
+                _ => &[Family::Inet, Family::Inet6],
+            };
+            for family in endpoint_families {
+                if !families.contains(family) {
+                    families.push(*family);
+                }
+            }
+        }
+        families.sort();
+        families
+    }
+
+    /// Whether the effect looks up a hostname, either explicitly or to connect to or bind it
+    pub fn resolves(&self) -> bool {
+        self.kind == NetworkEffectKind::Resolve
+            || self.endpoints.iter().any(|endpoint| endpoint.ip().is_none())
+    }
+}
+
+// Every combination of the alternatives of a host and a port
+fn endpoints(host: &VariableState, port: &VariableState) -> Vec<Endpoint> {
+    let mut endpoints = Vec::new();
+    for host in host.alternatives.iter() {
+        for port in port.alternatives.iter() {
+            let endpoint = Endpoint {
+                host: host.exact(),
+                port: port.exact().and_then(|port| port.parse().ok()),
+            };
+            if !endpoints.contains(&endpoint) {
+                endpoints.push(endpoint);
+            }
+        }
+    }
+    endpoints
+}
+
+/// Every side effect that uses the network
+pub fn network_effects<'s>(side_effects: &'s [SideEffect], registry: &EffectRegistry) -> Vec<NetworkEffect<'s>> {
+    side_effects.iter().filter_map(|side_effect| NetworkEffect::from_side_effect(side_effect, registry)).collect()
+}
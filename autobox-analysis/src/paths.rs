@@ -0,0 +1,120 @@
+use autobox_effect_parser::registry::{EffectRegistry, ParamKind};
+
+use crate::{Alternative, Piece, SideEffect, VariableState};
+
+/// What paths are resolved against
+#[derive(Debug, Clone, Default)]
+pub struct PathContext {
+    /// What `~` expands to, `HOME` from `--assume-env`. `~` is kept when it isn't known
+    pub home: Option<String>,
+    /// The directory relative paths are in, from `--working-dir`. They stay relative when it isn't known
+    pub working_dir: Option<String>,
+}
+
+/// Normalize the `path` arguments of effects in the registry, and `program`s run by a path rather
+/// than a name, ie: `~/./a//b/../c` is `/home/svc/a/c` when the home directory is `/home/svc`
+pub fn normalize_side_effects(side_effects: &mut [SideEffect], context: &PathContext, registry: &EffectRegistry) {
+    for side_effect in side_effects.iter_mut() {
+        let effect = match registry.get(&side_effect.name) {
+            Some(effect) => effect,
+            None => continue,
+        };
+        for (param, argument) in effect.params.iter().zip(side_effect.arguments.iter_mut()) {
+            // A program without a `/` is looked up in `PATH`, so it isn't a path yet
+            let is_path = param.kind == ParamKind::Path
+                || (param.kind == ParamKind::Program && has_separator(argument));
+            if is_path {
+                *argument = normalize(argument, context);
+            }
+        }
+    }
+}
+
+/// Every alternative of `path` with `~` expanded, relative paths resolved against the working
+/// directory, `.` and `..` resolved and duplicate `/`s collapsed
+pub fn normalize(path: &VariableState, context: &PathContext) -> VariableState {
+    let mut normalized = VariableState {
+        alternatives: Vec::new(),
+        ..path.clone()
+    };
+    for alternative in path.alternatives.iter() {
+        normalized.add_alternative(normalize_alternative(alternative, context));
+    }
+    normalized
+}
+
+fn normalize_alternative(alternative: &Alternative, context: &PathContext) -> Alternative {
+    let mut pieces = alternative.pieces();
+    if pieces.is_empty() {
+        return alternative.clone();
+    }
+
+    // `~` or `~/...`, but not `~user`
+    let home = pieces[0] == Piece::Char('~') && matches!(pieces.get(1), None | Some(Piece::Char('/')));
+    match (&pieces[0], &context.home, &context.working_dir) {
+        (_, Some(home_dir), _) if home => {
+            pieces.splice(..1, home_dir.chars().map(Piece::Char));
+        }
+        // A path starting with a hole or class could be absolute, so only known names are relative
+        (Piece::Char(c), _, Some(working_dir)) if *c != '/' && !home => {
+            let prefix: Vec<_> = working_dir.chars().chain(std::iter::once('/')).map(Piece::Char).collect();
+            pieces.splice(..0, prefix);
+        }
+        _ => {}
+    }
+    let keeps_home = home && context.home.is_none();
+
+    let absolute = pieces[0] == Piece::Char('/');
+    let trailing_slash = pieces.len() > 1 && pieces.last() == Some(&Piece::Char('/'));
+    let mut components: Vec<&[Piece]> = Vec::new();
+    for component in pieces.split(|piece| *piece == Piece::Char('/')) {
+        match name(component).as_deref() {
+            Some("") | Some(".") => {}
+            Some("..") => match components.last() {
+                // A hole or class could be several components, or `..` itself, so only known names
+                // are removed. `~` stays, since what's above it isn't known
+                Some(last) if name(last).is_some_and(|last| last != "..")
+                    && !(keeps_home && components.len() == 1) => {
+                    components.pop();
+                }
+                // `/..` is `/`
+                None if absolute => {}
+                _ => components.push(component),
+            },
+            _ => components.push(component),
+        }
+    }
+
+    let mut normalized = Alternative::default();
+    if absolute {
+        normalized.push_piece(Piece::Char('/'));
+    }
+    for (i, component) in components.iter().enumerate() {
+        if i > 0 {
+            normalized.push_piece(Piece::Char('/'));
+        }
+        for piece in component.iter() {
+            normalized.push_piece(piece.clone());
+        }
+    }
+    if components.is_empty() && !absolute {
+        normalized.push_piece(Piece::Char('.'));
+    }
+    // `dir/` is a directory, which engines like AppArmor treat differently from a file
+    if trailing_slash && !components.is_empty() {
+        normalized.push_piece(Piece::Char('/'));
+    }
+    normalized
+}
+
+// The name of a path component, when every character of it is known
+fn name(component: &[Piece]) -> Option<String> {
+    component.iter().map(|piece| match piece {
+        Piece::Char(c) => Some(*c),
+        _ => None,
+    }).collect()
+}
+
+fn has_separator(path: &VariableState) -> bool {
+    path.alternatives.iter().any(|alternative| alternative.pieces().contains(&Piece::Char('/')))
+}
@@ -0,0 +1,70 @@
+use autobox_effect_parser::registry::EffectRegistry;
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::network::network_effects;
+use crate::process::exec_effects;
+use crate::SideEffect;
+
+mod apparmor;
+mod landlock;
+mod nftables;
+mod seccomp;
+
+/// What to generate a sandbox policy for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEngine {
+    /// An AppArmor profile allowing the files, networks and programs used
+    Apparmor,
+    /// A seccomp policy in minijail's format, restricting socket families and network syscalls
+    Seccomp,
+    /// Landlock (ABI v4) rules for the TCP ports bound and connected to, using the `landlock` crate
+    Landlock,
+    /// nftables chains allowing the program's traffic
+    Nftables,
+}
+
+/// How AppArmor confines the programs a profile runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ExecTransition {
+    /// `ix`, they're confined by the same profile
+    #[default]
+    Ix,
+    /// `px`, they're confined by their own profile, which must exist
+    Px,
+}
+
+/// Choices the generated policy can't infer from the side effects
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PolicyOptions {
+    /// How AppArmor confines the programs run
+    pub exec_transition: ExecTransition,
+    /// Allow the `execve` that starts the program, for seccomp filters installed before it
+    pub exec_once: bool,
+}
+
+/// A policy allowing `side_effects`, and nothing else that the engine can restrict. What each
+/// effect means to the sandbox comes from its mappings in the registry
+pub fn generate(engine: PolicyEngine, options: PolicyOptions, registry: &EffectRegistry, side_effects: &[SideEffect]) -> String {
+    let network = network_effects(side_effects, registry);
+    let exec = exec_effects(side_effects, registry);
+    match engine {
+        PolicyEngine::Apparmor => apparmor::generate(side_effects, registry, &network, &exec, options.exec_transition),
+        PolicyEngine::Seccomp => seccomp::generate(&network, &exec, options.exec_once),
+        PolicyEngine::Landlock => landlock::generate(&network),
+        PolicyEngine::Nftables => nftables::generate(&network),
+    }
+}
+
+// `connect("10.0.0.1:443") in main -> fetch`, for comments saying where a rule comes from
+fn describe(side_effect: &SideEffect) -> String {
+    match side_effect.call_path.is_empty() {
+        true => side_effect.to_string(),
+        false => format!("{} in {}", side_effect, side_effect.call_path.join(" -> ")),
+    }
+}
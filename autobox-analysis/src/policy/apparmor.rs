@@ -0,0 +1,174 @@
+use std::fmt::Write;
+
+use autobox_effect_parser::registry::{EffectRegistry, ParamKind, SandboxMapping};
+
+use crate::network::{Family, NetworkEffect, NetworkEffectKind, Protocol};
+use crate::process::ExecEffect;
+use crate::{Alternative, HoleOrigin, SideEffect, VariableStateConstraint};
+use super::{describe, ExecTransition};
+
+// The rules of a profile, each after a comment saying which effect needs it
+#[derive(Default)]
+struct Rules {
+    lines: Vec<String>,
+    rules: Vec<String>,
+}
+
+impl Rules {
+    fn allow(&mut self, rule: String, comment: String) {
+        if self.rules.contains(&rule) {
+            return;
+        }
+        self.lines.push(format!("# {}", comment));
+        self.lines.push(rule.clone());
+        self.rules.push(rule);
+    }
+}
+
+/// An AppArmor profile with a rule for every alternative of every path, network and program the
+/// side effects use
+pub fn generate(
+    side_effects: &[SideEffect],
+    registry: &EffectRegistry,
+    network: &[NetworkEffect],
+    exec: &[ExecEffect],
+    exec_transition: ExecTransition,
+) -> String {
+    let mut rules = Rules::default();
+    for side_effect in side_effects {
+        let effect = match registry.get(&side_effect.name) {
+            Some(effect) => effect,
+            None => continue,
+        };
+        // Every path the effect takes gets the permissions of all of its `file(..)` mappings
+        let mut permissions = String::new();
+        for mapping in effect.mappings.iter() {
+            if let SandboxMapping::File(mapped) = mapping {
+                permissions.extend(mapped.chars().filter(|c| !permissions.contains(*c)).collect::<Vec<_>>());
+            }
+        }
+        if permissions.is_empty() {
+            continue;
+        }
+        let paths = effect.params.iter().zip(side_effect.arguments.iter())
+            .filter(|(param, _)| param.kind == ParamKind::Path);
+        for (_, path) in paths {
+            for alternative in path.alternatives.iter() {
+                rules.allow(format!("{} {},", quoted(&path_glob(alternative)), permissions), describe(side_effect));
+            }
+        }
+    }
+
+    for effect in network {
+        let kind = match (effect.kind, effect.protocol) {
+            // DNS is usually UDP, and TCP for large responses
+            (NetworkEffectKind::Resolve, _) => vec!["dgram", "stream"],
+            (_, Protocol::Tcp) => vec!["stream"],
+            (_, Protocol::Udp) => vec!["dgram"],
+        };
+        for family in effect.families() {
+            let family = match family {
+                Family::Inet => "inet",
+                Family::Inet6 => "inet6",
+            };
+            for kind in kind.iter() {
+                rules.allow(format!("network {} {},", family, kind), describe(effect.side_effect));
+            }
+        }
+    }
+
+    let transition = match exec_transition {
+        ExecTransition::Ix => "ix",
+        ExecTransition::Px => "px",
+    };
+    for effect in exec {
+        for alternative in effect.program.alternatives.iter() {
+            rules.allow(format!("{} {},", quoted(&program_glob(alternative)), transition), describe(effect.side_effect));
+        }
+    }
+
+    let mut profile = String::new();
+    writeln!(profile, "# AppArmor profile for the entrypoint's side effects, rename it and attach it to the program's path").unwrap();
+    writeln!(profile, "#include <tunables/global>").unwrap();
+    writeln!(profile).unwrap();
+    writeln!(profile, "profile autobox {{").unwrap();
+    writeln!(profile, "  #include <abstractions/base>").unwrap();
+    for line in rules.lines.iter() {
+        writeln!(profile, "  {}", line).unwrap();
+    }
+    writeln!(profile, "}}").unwrap();
+    profile
+}
+
+// The alternative as an AppArmor glob. Holes can hold a `/`, so they're `**`
+fn glob(alternative: &Alternative) -> String {
+    let mut glob = String::new();
+    for constraint in alternative.constraints.iter() {
+        match constraint {
+            VariableStateConstraint::Hole(_) => glob.push_str("**"),
+            VariableStateConstraint::Class(class, _) => match class.glob().as_str() {
+                "*" if class.alphabet.iter().any(|(start, end)| (*start..=*end).contains(&'/')) => glob.push_str("**"),
+                class_glob => glob.push_str(class_glob),
+            },
+            VariableStateConstraint::Value(value) => {
+                for c in value.chars() {
+                    if ['*', '?', '[', ']', '{', '}', '^', '\\', '"'].contains(&c) {
+                        glob.push('\\');
+                    }
+                    glob.push(c);
+                }
+            }
+        }
+    }
+    glob
+}
+
+// The alternative as an absolute path. The home directory, `~` or a leading `$HOME`, is
+// `@{HOME}`, and relative paths could be in any directory
+fn path_glob(alternative: &Alternative) -> String {
+    let mut rest = alternative.clone();
+    let home = match rest.constraints.first_mut() {
+        Some(VariableStateConstraint::Hole(HoleOrigin::EnvVar(Some(name)))) if name == "HOME" => {
+            rest.constraints.remove(0);
+            true
+        }
+        Some(VariableStateConstraint::Value(value)) if value == "~" || value.starts_with("~/") => {
+            value.remove(0);
+            true
+        }
+        _ => false,
+    };
+    let rest = glob(&rest);
+    match home && (rest.is_empty() || rest.starts_with('/')) {
+        true => format!("@{{HOME}}{}", rest),
+        false => absolute(glob(alternative)),
+    }
+}
+
+// A glob that may be relative as an absolute one
+fn absolute(glob: String) -> String {
+    if glob.starts_with('/') {
+        glob
+    } else if glob.starts_with("**") {
+        format!("/{}", glob)
+    } else {
+        format!("/**/{}", glob.trim_start_matches("./"))
+    }
+}
+
+// The path of a program, which is looked up in the usual `PATH` when it's just a name, ie: `git`
+fn program_glob(alternative: &Alternative) -> String {
+    let glob = glob(alternative);
+    if glob.contains('/') || glob.starts_with("**") {
+        path_glob(alternative)
+    } else {
+        format!("/{{,usr/}}{{,local/}}{{,s}}bin/{}", glob)
+    }
+}
+
+fn quoted(path: &str) -> String {
+    match path.contains(char::is_whitespace) {
+        true => format!("\"{}\"", path),
+        false => path.to_string(),
+    }
+}
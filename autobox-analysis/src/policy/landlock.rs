@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use crate::network::{NetworkEffect, NetworkEffectKind, Protocol};
+use super::describe;
+
+// The ports allowed for one of Landlock's network accesses, ie: `BindTcp`
+struct AccessRules {
+    access: &'static str,
+    /// Each port, with the effect that first needed it
+    ports: Vec<(u16, String)>,
+    /// An effect using a port we don't know, which means the access can't be restricted at all
+    unrestricted: Option<String>,
+}
+
+impl AccessRules {
+    fn new(access: &'static str) -> Self {
+        Self {
+            access,
+            ports: Vec::new(),
+            unrestricted: None,
+        }
+    }
+
+    fn allow(&mut self, port: Option<u16>, effect: &NetworkEffect) {
+        match port {
+            Some(port) if self.ports.iter().any(|(allowed, _)| *allowed == port) => {}
+            Some(port) => self.ports.push((port, describe(effect.side_effect))),
+            None => {
+                self.unrestricted.get_or_insert_with(|| describe(effect.side_effect));
+            }
+        }
+    }
+}
+
+/// Rust code restricting the TCP ports the program binds and connects to, with the `landlock` crate.
+/// Landlock can't restrict hosts or UDP, and an access with any unknown port isn't restricted
+pub fn generate(network: &[NetworkEffect]) -> String {
+    let mut bind = AccessRules::new("BindTcp");
+    let mut connect = AccessRules::new("ConnectTcp");
+    let mut unsupported = Vec::new();
+    for effect in network {
+        match (effect.kind, effect.protocol) {
+            (NetworkEffectKind::Bind, Protocol::Tcp) => {
+                for endpoint in effect.endpoints.iter() {
+                    bind.allow(endpoint.port, effect);
+                }
+            }
+            (NetworkEffectKind::Connect, Protocol::Tcp) => {
+                for endpoint in effect.endpoints.iter() {
+                    connect.allow(endpoint.port, effect);
+                }
+            }
+            // Accepting connections is allowed by binding
+            (NetworkEffectKind::Listen, _) => {}
+            // DNS is UDP, which is covered below
+            (NetworkEffectKind::Resolve, _) => {}
+            _ => unsupported.push(describe(effect.side_effect)),
+        }
+        // DNS falls back to TCP for large responses
+        if effect.resolves() {
+            connect.allow(Some(53), effect);
+        }
+    }
+
+    let mut code = String::new();
+    writeln!(code, "// Landlock (ABI v4) network rules for the entrypoint's side effects, with the `landlock` crate").unwrap();
+    for effect in unsupported {
+        writeln!(code, "// {} isn't restricted, Landlock only handles TCP", effect).unwrap();
+    }
+    let mut handled = Vec::new();
+    for rules in [&bind, &connect] {
+        match rules.unrestricted {
+            Some(ref effect) => writeln!(code, "// {} isn't restricted, {} uses an unknown port", rules.access, effect).unwrap(),
+            None => handled.push(rules),
+        }
+    }
+    if handled.is_empty() {
+        return code;
+    }
+
+    writeln!(code, "use landlock::{{AccessNet, NetPort, Ruleset, RulesetAttr, RulesetCreatedAttr}};").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "Ruleset::default()").unwrap();
+    for rules in handled.iter() {
+        writeln!(code, "    .handle_access(AccessNet::{})?", rules.access).unwrap();
+    }
+    writeln!(code, "    .create()?").unwrap();
+    for rules in handled.iter() {
+        for (port, effect) in rules.ports.iter() {
+            writeln!(code, "    // {}", effect).unwrap();
+            writeln!(code, "    .add_rule(NetPort::new({}, AccessNet::{}))?", port, rules.access).unwrap();
+        }
+    }
+    writeln!(code, "    .restrict_self()?;").unwrap();
+    code
+}
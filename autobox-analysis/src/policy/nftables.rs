@@ -0,0 +1,90 @@
+use std::fmt::Write;
+use std::net::IpAddr;
+
+use crate::network::{Endpoint, NetworkEffect, NetworkEffectKind, Protocol};
+use super::describe;
+
+// The rules of a chain, each after a comment saying which effect needs it
+#[derive(Default)]
+struct Chain {
+    lines: Vec<String>,
+    rules: Vec<String>,
+}
+
+impl Chain {
+    fn allow(&mut self, rule: String, comment: String) {
+        if self.rules.contains(&rule) {
+            return;
+        }
+        self.lines.push(format!("# {}", comment));
+        self.lines.push(rule.clone());
+        self.rules.push(rule);
+    }
+
+    fn write(&self, out: &mut String, name: &str) {
+        writeln!(out, "    chain {} {{", name).unwrap();
+        writeln!(out, "        ct state established,related accept").unwrap();
+        for line in self.lines.iter() {
+            writeln!(out, "        {}", line).unwrap();
+        }
+        writeln!(out, "        reject").unwrap();
+        writeln!(out, "    }}").unwrap();
+    }
+}
+
+// ie: `ip daddr 10.0.0.1 tcp dport 443 accept`. Hosts that aren't addresses only match by port
+fn rule(endpoint: &Endpoint, protocol: Protocol) -> String {
+    let protocol = match protocol {
+        Protocol::Tcp => "tcp",
+        Protocol::Udp => "udp",
+    };
+    let mut rule = String::new();
+    match endpoint.ip() {
+        Some(ip) if ip.is_unspecified() => {}
+        Some(IpAddr::V4(ip)) => write!(rule, "ip daddr {} ", ip).unwrap(),
+        Some(IpAddr::V6(ip)) => write!(rule, "ip6 daddr {} ", ip).unwrap(),
+        None => {}
+    }
+    match endpoint.port {
+        Some(port) => write!(rule, "{} dport {} accept", protocol, port).unwrap(),
+        None => write!(rule, "meta l4proto {} accept", protocol).unwrap(),
+    }
+    rule
+}
+
+/// An `inet` table with chains accepting only the traffic of the effects, one rule per endpoint
+pub fn generate(network: &[NetworkEffect]) -> String {
+    let mut output = Chain::default();
+    let mut input = Chain::default();
+    for effect in network {
+        let comment = describe(effect.side_effect);
+        for endpoint in effect.endpoints.iter() {
+            let comment = match (&endpoint.host, endpoint.ip()) {
+                (Some(host), None) => format!("{}, {} can only be matched by port", comment, host),
+                _ => comment.clone(),
+            };
+            match effect.kind {
+                NetworkEffectKind::Connect | NetworkEffectKind::SendTo => output.allow(rule(endpoint, effect.protocol), comment),
+                // Port 0 is an ephemeral port, which only gets replies that are already accepted
+                NetworkEffectKind::Bind | NetworkEffectKind::Listen if endpoint.port == Some(0) => {}
+                NetworkEffectKind::Bind | NetworkEffectKind::Listen => input.allow(rule(endpoint, effect.protocol), comment),
+                NetworkEffectKind::Resolve => {}
+            }
+        }
+        if effect.resolves() {
+            output.allow("udp dport 53 accept".to_string(), format!("DNS for {}", comment));
+            output.allow("tcp dport 53 accept".to_string(), format!("DNS for {}", comment));
+        }
+    }
+
+    let mut table = String::new();
+    writeln!(table, "# Chains accepting the traffic of the entrypoint's side effects. Jump to them from base chains").unwrap();
+    writeln!(table, "# of this table for the program's traffic, ie:").unwrap();
+    writeln!(table, "# `socket cgroupv2 level 2 \"system.slice/app.service\" jump autobox_output`").unwrap();
+    writeln!(table, "table inet autobox {{").unwrap();
+    output.write(&mut table, "autobox_output");
+    writeln!(table).unwrap();
+    input.write(&mut table, "autobox_input");
+    writeln!(table, "}}").unwrap();
+    table
+}
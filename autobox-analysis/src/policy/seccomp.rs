@@ -0,0 +1,83 @@
+use std::fmt::Write;
+
+use crate::network::{Family, NetworkEffect, NetworkEffectKind};
+use crate::process::ExecEffect;
+use super::describe;
+
+/// The syscalls std makes to run a program
+const EXEC_SYSCALLS: &[&str] = &["clone", "clone3", "vfork", "execve", "execveat", "pipe2", "dup2", "wait4", "waitid"];
+
+// The syscalls std and tokio make for each kind of effect, on top of `socket`
+fn syscalls(kind: NetworkEffectKind) -> &'static [&'static str] {
+    match kind {
+        NetworkEffectKind::Connect => &["connect", "getsockopt", "getpeername", "getsockname", "sendto", "recvfrom", "shutdown"],
+        NetworkEffectKind::Bind => &["bind", "setsockopt", "getsockname"],
+        NetworkEffectKind::Listen => &["listen", "accept", "accept4"],
+        NetworkEffectKind::Resolve => &["connect", "sendto", "sendmmsg", "recvfrom"],
+        NetworkEffectKind::SendTo => &["sendto", "recvfrom"],
+    }
+}
+
+/// A minijail policy allowing sockets of only the families the effects use, ie: `AF_INET` for
+/// `connect("10.0.0.1:443")`, the syscalls they make with them, and `execve` only if a program is run.
+/// With `exec_once`, the `execve` starting the program is allowed even if it runs nothing itself
+pub fn generate(network: &[NetworkEffect], exec: &[ExecEffect], exec_once: bool) -> String {
+    let mut policy = String::new();
+    writeln!(policy, "# Network and exec syscalls of the entrypoint's side effects, in minijail's policy format.").unwrap();
+    writeln!(policy, "# Combine this with the rest of the program's policy, syscalls that aren't listed are denied").unwrap();
+    network_syscalls(&mut policy, network);
+
+    if !exec.is_empty() {
+        for effect in exec {
+            writeln!(policy, "# {}", describe(effect.side_effect)).unwrap();
+        }
+        writeln!(policy, "# seccomp can't tell which program is run, restrict that with the AppArmor profile").unwrap();
+        for syscall in EXEC_SYSCALLS {
+            writeln!(policy, "{}: 1", syscall).unwrap();
+        }
+    } else if exec_once {
+        writeln!(policy, "# No programs are run, this is the exec starting the program, ie: under `minijail0 -S`").unwrap();
+        writeln!(policy, "execve: 1").unwrap();
+    } else {
+        writeln!(policy, "# No programs are run, so execve isn't allowed").unwrap();
+    }
+    policy
+}
+
+fn network_syscalls(policy: &mut String, network: &[NetworkEffect]) {
+    if network.is_empty() {
+        writeln!(policy, "# There are no network side effects, so no sockets are allowed").unwrap();
+        return;
+    }
+
+    let mut families = Vec::new();
+    let mut allowed = Vec::new();
+    for effect in network {
+        writeln!(policy, "# {}", describe(effect.side_effect)).unwrap();
+        for family in effect.families() {
+            if !families.contains(&family) {
+                families.push(family);
+            }
+        }
+        let mut kinds = vec![effect.kind];
+        if effect.resolves() {
+            kinds.push(NetworkEffectKind::Resolve);
+        }
+        for syscall in kinds.into_iter().flat_map(syscalls) {
+            if !allowed.contains(syscall) {
+                allowed.push(*syscall);
+            }
+        }
+    }
+
+    families.sort();
+    let families: Vec<_> = families.iter().map(|family| match family {
+        Family::Inet => "arg0 == AF_INET",
+        Family::Inet6 => "arg0 == AF_INET6",
+    }).collect();
+    // Other families fail rather than kill the program, ie: glibc trying nscd's unix socket
+    writeln!(policy, "socket: {}; return EPERM", families.join(" || ")).unwrap();
+    for syscall in allowed {
+        writeln!(policy, "{}: 1", syscall).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use autobox_effect_parser::registry::{EffectRegistry, ParamKind, SandboxMapping};
+
+use crate::{SideEffect, VariableState};
+
+/// Methods of `std::process::Command` that run it. `exec` is `CommandExt::exec`
+pub const RUN_METHODS: &[&str] = &["spawn", "output", "status", "exec"];
+
+/// A `std::process::Command` being built, ie: `Command::new("git").arg("status")`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessCommand {
+    pub program: VariableState,
+    pub args: Vec<VariableState>,
+    /// Set by `current_dir`, which relative programs are found in
+    pub current_dir: Option<VariableState>,
+}
+
+impl ProcessCommand {
+    pub fn new(program: VariableState) -> Self {
+        Self {
+            program,
+            args: Vec::new(),
+            current_dir: None,
+        }
+    }
+
+    /// A command that is either `self` or `other`, as long as they have as many arguments
+    pub fn join(&self, other: &ProcessCommand) -> Option<ProcessCommand> {
+        if self.args.len() != other.args.len() {
+            return None;
+        }
+        let current_dir = match (&self.current_dir, &other.current_dir) {
+            (Some(lhs), Some(rhs)) => Some(lhs.join(rhs)),
+            _ => None,
+        };
+        Some(Self {
+            program: self.program.join(&other.program),
+            args: self.args.iter().zip(other.args.iter()).map(|(l, r)| l.join(r)).collect(),
+            current_dir,
+        })
+    }
+
+    /// The `exec(program, args...)` side effect of running the command. A relative program with a
+    /// `/` in it, ie: `./bin/tool`, is found in the `current_dir`
+    pub fn exec(&self) -> SideEffect {
+        let mut program = self.program.clone();
+        let relative = program.alternatives.iter().all(|alternative| {
+            matches!(alternative.exact(), Some(path) if path.contains('/') && !path.starts_with('/'))
+        });
+        if let (true, Some(current_dir)) = (relative, &self.current_dir) {
+            let stripped = program.alternatives.iter()
+                .filter_map(|alternative| alternative.exact())
+                .map(|path| VariableState::value(path.trim_start_matches("./").to_string()))
+                .reduce(|union, path| union.join(&path));
+            if let Some(stripped) = stripped {
+                program = VariableState::joined(&[current_dir.clone(), stripped], "/");
+            }
+        }
+        SideEffect {
+            name: "exec".to_string(),
+            arguments: std::iter::once(program).chain(self.args.iter().cloned()).collect(),
+            repeated: false,
+            imprecise: false,
+            call_path: Vec::new(),
+            call_sites: Vec::new(),
+            derivations: Vec::new(),
+        }
+    }
+}
+
+// Command::new("git")
+pub fn is_command_new(func: &syn::Expr) -> bool {
+    match func {
+        syn::Expr::Path(path) => {
+            let segments: Vec<_> = path.path.segments.iter().rev().take(2).map(|segment| segment.ident.to_string()).collect();
+            matches!(segments.as_slice(), [new, ty] if ty == "Command" && new == "new")
+        }
+        _ => false,
+    }
+}
+
+/// A side effect running a program, `exec(program, args...)`
+#[derive(Debug)]
+pub struct ExecEffect<'s> {
+    pub program: &'s VariableState,
+    pub side_effect: &'s SideEffect,
+}
+
+/// Every side effect the registry maps to `exec`
+pub fn exec_effects<'s>(side_effects: &'s [SideEffect], registry: &EffectRegistry) -> Vec<ExecEffect<'s>> {
+    side_effects.iter()
+        .filter_map(|side_effect| {
+            let effect = registry.mapped_to(&side_effect.name, &SandboxMapping::Exec)?;
+            let program = effect.param_index(ParamKind::Program).unwrap_or(0);
+            Some(ExecEffect {
+                program: side_effect.arguments.get(program)?,
+                side_effect,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::analyzer::AnalysisReport;
+use crate::gaps::{Gap, GapKind};
+use crate::taint::{TaintSource, DEFAULT_SINKS};
+use crate::{Location, SideEffect};
+
+/// Every rule a result can be reported under, with its level and a description
+const RULES: &[(&str, &str, &str)] = &[
+    ("side-effect", "note", "A side effect reachable from the entrypoint"),
+    ("declaration-mismatch", "warning", "An effect the registry doesn't know, or that is given the wrong number of arguments"),
+    ("tainted-argument", "error", "An argument of a sensitive side effect holds a value from an untrusted source"),
+    ("unsupported-construct", "warning", "Syntax the analysis doesn't support, which may hide side effects"),
+    ("unresolved-callee", "warning", "A call to a function the analysis couldn't find"),
+    ("undeclared-external", "warning", "A call to a function from another crate that has no declaration"),
+    ("unsafe-block", "warning", "An unsafe block, which can have effects the analysis can't see"),
+    ("ffi-call", "warning", "A call to a function from an extern block"),
+    ("inline-asm", "warning", "Inline assembly, which can make any syscall"),
+    ("recursive-call", "warning", "A recursive call, which isn't followed again"),
+    ("unloaded-module", "warning", "A module in another file, which isn't analyzed"),
+];
+
+/// The report as a SARIF 2.1.0 log: every side effect, declaration warning, gap and argument of a
+/// default sink tainted by any source, located in the analyzed file or the declarations file they're in
+pub fn log(report: &AnalysisReport) -> String {
+    let uri = uri(&report.source);
+    let uri = uri.as_str();
+    let mut results = Vec::new();
+
+    for side_effect in report.side_effects.iter() {
+        let message = format!("{} in {}", side_effect, side_effect.call_path.join(" -> "));
+        results.push(result("side-effect", message, side_effect.call_sites.last(), call_chain(side_effect, uri), uri));
+    }
+    for warning in report.warnings.iter() {
+        let location = Location {
+            file: warning.file.clone(),
+            line: warning.line,
+            column: warning.column,
+            end_line: warning.end_line,
+            end_column: warning.end_column,
+        };
+        let message = format!("in `{}`: {}", warning.fn_name, warning.message);
+        results.push(result("declaration-mismatch", message, Some(&location), Vec::new(), uri));
+    }
+    let sinks: Vec<_> = DEFAULT_SINKS.iter().map(ToString::to_string).collect();
+    for finding in report.tainted(TaintSource::ALL, &sinks) {
+        let sources: Vec<_> = finding.sources.iter().map(ToString::to_string).collect();
+        let message = format!(
+            "argument {} of {} holds a value from {}, called through {}",
+            finding.argument,
+            finding.side_effect,
+            sources.join("; "),
+            finding.side_effect.call_path.join(" -> "),
+        );
+        let related = call_chain(finding.side_effect, uri);
+        results.push(result("tainted-argument", message, finding.side_effect.call_sites.last(), related, uri));
+    }
+    for gap in report.gaps.iter() {
+        results.push(gap_result(gap, uri));
+    }
+
+    let mut artifacts = vec![json!({ "location": { "uri": uri } })];
+    for file in report.declarations.iter() {
+        artifacts.push(json!({ "location": { "uri": self::uri(file) } }));
+    }
+    let rules: Vec<_> = RULES.iter()
+        .map(|(id, level, description)| json!({
+            "id": id,
+            "shortDescription": { "text": description },
+            "defaultConfiguration": { "level": level },
+        }))
+        .collect();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "autobox",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "artifacts": artifacts,
+            "results": results,
+        }],
+    });
+    format!("{:#}\n", log)
+}
+
+fn result(rule_id: &str, message: String, location: Option<&Location>, related: Vec<Value>, uri: &str) -> Value {
+    let rule_index = RULES.iter().position(|(id, _, _)| *id == rule_id).expect("every rule is in RULES");
+    let mut result = json!({
+        "ruleId": rule_id,
+        "ruleIndex": rule_index,
+        "level": RULES[rule_index].1,
+        "message": { "text": message },
+    });
+    if let Some(location) = location {
+        result["locations"] = json!([physical_location(location, uri)]);
+    }
+    if !related.is_empty() {
+        result["relatedLocations"] = Value::Array(related);
+    }
+    result
+}
+
+fn gap_result(gap: &Gap, uri: &str) -> Value {
+    let rule_id = match gap.kind {
+        GapKind::Unsupported(_) => "unsupported-construct",
+        GapKind::UnresolvedCallee(_) => "unresolved-callee",
+        GapKind::UndeclaredExternal(_) => "undeclared-external",
+        GapKind::Unsafe => "unsafe-block",
+        GapKind::Ffi(_) => "ffi-call",
+        GapKind::InlineAsm => "inline-asm",
+        GapKind::Recursion(_) => "recursive-call",
+        GapKind::UnloadedModule(_) => "unloaded-module",
+    };
+    let message = match gap.call_path.is_empty() {
+        true => gap.kind.to_string(),
+        false => format!("{} in {}", gap.kind, gap.call_path.join(" -> ")),
+    };
+    let location = Location {
+        file: gap.file.clone(),
+        line: gap.line,
+        column: gap.column,
+        end_line: gap.end_line,
+        end_column: gap.end_column,
+    };
+    result(rule_id, message, Some(&location), Vec::new(), uri)
+}
+
+// Where each function on the way to a side effect is called, starting with the entrypoint
+fn call_chain(side_effect: &SideEffect, uri: &str) -> Vec<Value> {
+    side_effect.call_path.iter()
+        .zip(side_effect.call_sites.iter())
+        .enumerate()
+        .map(|(id, (function, site))| {
+            let mut related = physical_location(site, uri);
+            related["id"] = json!(id);
+            let text = match id {
+                0 => format!("entrypoint `{}`", function),
+                _ => format!("call to `{}`", function),
+            };
+            related["message"] = json!({ "text": text });
+            related
+        })
+        .collect()
+}
+
+// The URI of `path`, relative to the working directory when it is
+fn uri(path: &Path) -> String {
+    let uri = path.to_string_lossy().replace('\\', "/");
+    uri.strip_prefix("./").map(str::to_string).unwrap_or(uri)
+}
+
+fn physical_location(location: &Location, uri: &str) -> Value {
+    let uri = location.file.as_deref().map(self::uri).unwrap_or_else(|| uri.to_string());
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": uri },
+            "region": {
+                "startLine": location.line,
+                "startColumn": location.column,
+                "endLine": location.end_line,
+                "endColumn": location.end_column,
+            },
+        },
+    })
+}
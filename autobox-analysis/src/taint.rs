@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+
+use crate::{HoleOrigin, SideEffect};
+
+/// Side effects that are dangerous to perform on values an attacker controls
+pub const DEFAULT_SINKS: &[&str] = &[
+    "reads_file", "writes_file", "deletes_file", "creates_dir", "exec", "connect", "bind", "send_to",
+];
+
+/// Where untrusted values come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum TaintSource {
+    /// Command line arguments
+    Args,
+    /// Environment variables
+    Env,
+    /// Standard input
+    Stdin,
+    /// Anything read from the network, ie: HTTP request bodies
+    Network,
+    /// The contents of files
+    File,
+}
+
+impl TaintSource {
+    /// Every source, which is what's checked when none are chosen
+    pub const ALL: &'static [TaintSource] = &[
+        TaintSource::Args, TaintSource::Env, TaintSource::Stdin, TaintSource::Network, TaintSource::File,
+    ];
+
+    pub(crate) fn taints(self, origin: &HoleOrigin) -> bool {
+        matches!(
+            (self, origin),
+            (TaintSource::Args, HoleOrigin::Argv)
+                | (TaintSource::Env, HoleOrigin::EnvVar(_))
+                | (TaintSource::Stdin, HoleOrigin::Stdin)
+                | (TaintSource::Network, HoleOrigin::NetworkRead)
+                | (TaintSource::File, HoleOrigin::FileRead)
+        )
+    }
+}
+
+/// An untrusted value that reached a variable, tracked apart from what's known about the value so
+/// that it survives the value being assumed (`--assume-env`) or narrowed to a declared pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Taint {
+    /// Where the value came from, ie: `HoleOrigin::EnvVar(Some("HOME"))`
+    pub origin: HoleOrigin,
+    /// What the value flowed through from its source, ie: `` `dir` ``, `` returned by `config_dir` ``
+    /// and `` `p` in `read` ``
+    pub flow: Vec<String>,
+}
+
+impl Taint {
+    /// A value straight from `origin`, when it's one of the sources
+    pub(crate) fn of(origin: &HoleOrigin) -> Option<Self> {
+        TaintSource::ALL.iter().any(|source| source.taints(origin)).then(|| Self {
+            origin: origin.clone(),
+            flow: Vec::new(),
+        })
+    }
+}
+
+impl Display for Taint {
+    /// ie: ``environment variable HOME via `dir` -> returned by `config_dir` -> `p` in `read` ``
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.origin)?;
+        if !self.flow.is_empty() {
+            write!(f, " via {}", self.flow.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A tainted value reaching an argument of a sensitive side effect
+#[derive(Debug)]
+pub struct TaintFinding<'s> {
+    /// The sensitive side effect
+    pub side_effect: &'s SideEffect,
+    /// The position of the tainted argument
+    pub argument: usize,
+    /// Every untrusted value in the argument, with how it got there
+    pub sources: Vec<&'s Taint>,
+}
+
+impl Display for TaintFinding<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let argument = &self.side_effect.arguments[self.argument];
+        writeln!(f, "Tainted argument {} of {}: {}", self.argument, self.side_effect, argument)?;
+        for source in self.sources.iter() {
+            writeln!(f, "    from {}", source)?;
+        }
+        write!(f, "    called through {}", self.side_effect.call_path.join(" -> "))
+    }
+}
+
+/// Find every argument of a side effect named in `sinks` that holds a value from one of `sources`
+pub fn find_tainted<'s>(
+    side_effects: &'s [SideEffect],
+    sources: &[TaintSource],
+    sinks: &[String],
+) -> Vec<TaintFinding<'s>> {
+    let mut findings = Vec::new();
+    for side_effect in side_effects.iter().filter(|side_effect| sinks.contains(&side_effect.name)) {
+        for (argument, state) in side_effect.arguments.iter().enumerate() {
+            let tainted: Vec<_> = state.taints().iter()
+                .filter(|taint| sources.iter().any(|source| source.taints(&taint.origin)))
+                .collect();
+            if !tainted.is_empty() {
+                findings.push(TaintFinding {
+                    side_effect,
+                    argument,
+                    sources: tainted,
+                });
+            }
+        }
+    }
+    findings
+}
@@ -0,0 +1,13 @@
+use std::ffi::OsString;
+
+// Cargo runs `cargo autobox <args>` as `cargo-autobox autobox <args>`, which is
+// `autobox-cli workspace <args>`
+fn main() -> eyre::Result<()> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    if args.get(1).is_some_and(|arg| arg == "autobox") {
+        args.remove(1);
+    }
+    args.insert(1, OsString::from("workspace"));
+    let exit_code = autobox_cli::run(args)?;
+    std::process::exit(exit_code)
+}
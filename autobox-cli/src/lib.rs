@@ -0,0 +1,528 @@
+//! The command line of `autobox-cli`, and of `cargo autobox` which analyzes a workspace
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use eyre::{bail, Result};
+
+use autobox_analysis::{
+    workspace_binaries, AnalysisReport, Analyzer, Baseline, BaselineChange, BaselineEffect, Config, ExecTransition, GapKind,
+    GraphFormat, HoleOrigin, PolicyEngine, PolicyOptions, ReportFormat, SideEffect, TaintSource, CONFIG_TEMPLATE,
+    DEFAULT_BASELINE, DEFAULT_CONFIG, DEFAULT_SINKS,
+};
+
+/// The file analyzed when none is given
+const DEFAULT_SOURCE: &str = "./example-app/src/main.rs";
+
+/// `taint` found side effects with untrusted arguments
+const EXIT_TAINTED: i32 = 1;
+
+/// `check` found side effects the baseline doesn't cover
+const EXIT_NOT_IN_BASELINE: i32 = 1;
+
+/// The analysis has gaps, so side effects may be missing
+const EXIT_UNSOUND: i32 = 2;
+
+/// `workspace` couldn't analyze some of the binaries
+const EXIT_FAILED: i32 = 1;
+
+#[derive(Debug, Parser)]
+#[command(about = "Infer the side effects of a program to generate its sandbox")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Assume an environment variable has a value rather than being unknown, ie: `HOME=/home/svc`
+    #[arg(long = "assume-env", global = true, value_parser = parse_env_assignment)]
+    assume_env: Vec<(String, String)>,
+    /// The directory the program runs in, which relative paths are resolved against
+    #[arg(long = "working-dir", global = true)]
+    working_dir: Option<String>,
+    /// A registry of effects to add to the builtin ones, `autobox.effects` when it exists
+    #[arg(long = "effects", global = true)]
+    effects: Option<PathBuf>,
+    /// List everything the analysis couldn't model, rather than how many there are of each
+    #[arg(long = "explain-gaps", global = true)]
+    explain_gaps: bool,
+    /// The configuration, `autobox.toml` in the crate or a directory above it when not given
+    #[arg(long = "config", global = true)]
+    config: Option<PathBuf>,
+}
+
+// `HOME=/home/svc` for `--assume-env`
+fn parse_env_assignment(assignment: &str) -> std::result::Result<(String, String), String> {
+    match assignment.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, found `{}`", assignment)),
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print every side effect reachable from the entrypoint
+    Analyze {
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+        /// How to print what was found, `text` unless `autobox.toml` has a `format`
+        #[arg(long, value_enum)]
+        format: Option<ReportFormat>,
+    },
+    /// Explain where a side effect comes from: the calls that reach it and how its arguments were derived
+    Explain {
+        /// The number of the side effect in the output of `analyze`
+        index: usize,
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+    },
+    /// Print the calls reachable from the entrypoint, with the side effects reached through each
+    Callgraph {
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        format: GraphFormat,
+    },
+    /// Report side effects whose arguments hold values from untrusted sources
+    Taint {
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+        /// Where untrusted values come from, every source when not given
+        #[arg(long = "source", value_enum)]
+        sources: Vec<TaintSource>,
+        /// Side effects that must not be given untrusted values, ie: `reads_file`
+        #[arg(long = "sink")]
+        sinks: Vec<String>,
+    },
+    /// Fail when there are side effects the baseline doesn't have, or with broader arguments
+    Check {
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+        /// The baseline, `autobox.lock` in the crate or the working directory when not given
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+    },
+    /// Manage the baseline of side effects that `check` compares against
+    Baseline {
+        #[command(subcommand)]
+        command: BaselineCommand,
+    },
+    /// Print a sandbox policy allowing the side effects reachable from the entrypoint
+    Generate {
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+        /// Needed unless `autobox.toml` has a `generate.policy_engine`
+        #[arg(long, value_enum)]
+        policy_engine: Option<PolicyEngine>,
+        /// How AppArmor confines the programs the entrypoint runs, `ix` when not configured
+        #[arg(long, value_enum)]
+        exec_transition: Option<ExecTransition>,
+        /// Allow the `execve` starting the program in seccomp policies, for filters installed before it
+        #[arg(long)]
+        exec_once: bool,
+    },
+    /// Analyze every binary of a Cargo workspace that has an `#[effect::entrypoint]`, which is what
+    /// `cargo autobox` runs
+    Workspace {
+        /// The `Cargo.toml` of the workspace, the one the working directory is in when not given
+        #[arg(long)]
+        manifest_path: Option<PathBuf>,
+        /// Only analyze the binaries with these names
+        #[arg(long = "bin")]
+        bins: Vec<String>,
+    },
+    /// Write an `autobox.toml` with every option documented and commented out
+    Init {
+        /// The crate or workspace to write it to
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Replace the `autobox.toml` that's already there
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Command {
+    /// The file or crate analyzed
+    fn file(&self) -> Option<&Path> {
+        match self {
+            Command::Analyze { file, .. }
+            | Command::Explain { file, .. }
+            | Command::Callgraph { file, .. }
+            | Command::Taint { file, .. }
+            | Command::Check { file, .. }
+            | Command::Baseline { command: BaselineCommand::Update { file, .. } }
+            | Command::Generate { file, .. } => Some(file),
+            Command::Workspace { .. } | Command::Init { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum BaselineCommand {
+    /// Replace the baseline with the side effects found now
+    Update {
+        /// The file with the `#[effect::entrypoint]`, or the crate it's in
+        #[arg(default_value = DEFAULT_SOURCE)]
+        file: PathBuf,
+        /// The baseline, `autobox.lock` in the crate or the working directory when not given
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+    },
+}
+
+// The baseline given, or the default one next to the registry
+fn baseline_path(file: &Path, baseline: Option<PathBuf>) -> PathBuf {
+    match baseline {
+        Some(baseline) => baseline,
+        None if file.is_dir() => file.join(DEFAULT_BASELINE),
+        None => PathBuf::from(DEFAULT_BASELINE),
+    }
+}
+
+// The configuration given with `--config`, or else the `autobox.toml` of the crate or a directory
+// above it. It's looked for from the directory of the file when a single file is analyzed
+fn load_config(config: Option<&Path>, file: &Path) -> Result<Config> {
+    let dir = match file.parent() {
+        _ if file.is_dir() => file,
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    match config.map(Path::to_path_buf).or_else(|| Config::find(dir)) {
+        Some(path) => Config::read(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+/// Run the command line given by `args`, starting with the program's name, returning the status
+/// to exit with
+pub fn run(args: impl IntoIterator<Item = OsString>) -> Result<i32> {
+    let mut cli = Cli::parse_from(args);
+    let command = cli.command.take().unwrap_or(Command::Analyze {
+        file: PathBuf::from(DEFAULT_SOURCE),
+        format: None,
+    });
+    let config = match command.file() {
+        Some(file) => load_config(cli.config.as_deref(), file)?,
+        None => Config::default(),
+    };
+    // Run `analyzer` with the global options, which override its configuration. Effects the registry
+    // doesn't know are warned about, since they won't be in any policy
+    let run_analyzer = |mut analyzer: Analyzer| -> Result<AnalysisReport> {
+        for (name, value) in cli.assume_env.iter() {
+            analyzer = analyzer.assume_env(name, value);
+        }
+        if let Some(working_dir) = &cli.working_dir {
+            analyzer = analyzer.working_dir(working_dir);
+        }
+        if let Some(effects) = &cli.effects {
+            analyzer = analyzer.effects_file(effects);
+        }
+        let report = analyzer.run()?;
+        for warning in report.warnings.iter() {
+            eprintln!("warning: {}:{}", report.file(&warning.file).display(), warning);
+        }
+        if !cli.explain_gaps {
+            for gap in report.gaps.iter().filter(|gap| matches!(gap.kind, GapKind::Unsupported(_))) {
+                eprintln!("warning: {}:{}", report.file(&gap.file).display(), gap);
+            }
+        }
+        Ok(report)
+    };
+    let analyze = |file: &PathBuf| run_analyzer(Analyzer::new(file).config(&config));
+
+    let mut exit_code = 0;
+    let report = match command {
+        Command::Analyze { file, format } => match format.or(config.format).unwrap_or_default() {
+            ReportFormat::Text => {
+                println!(
+                    "running from: {}",
+                    std::env::current_dir().unwrap().display()
+                );
+                let report = analyze(&file)?;
+                print_side_effects(&report);
+                print_env_vars(&report);
+                report
+            }
+            ReportFormat::Sarif => {
+                let report = analyze(&file)?;
+                print!("{}", report.sarif());
+                report
+            }
+        },
+        Command::Explain { index, file } => {
+            let report = analyze(&file)?;
+            match report.side_effects.get(index) {
+                Some(side_effect) => print_explanation(&report, index, side_effect),
+                None => bail!("there is no side effect {}, `analyze` found {}", index, report.side_effects.len()),
+            }
+            report
+        }
+        Command::Callgraph { file, format } => {
+            let report = analyze(&file)?;
+            print!("{}", report.call_graph.render(format));
+            report
+        }
+        Command::Taint { file, mut sources, mut sinks } => {
+            if sources.is_empty() {
+                sources = TaintSource::ALL.to_vec();
+            }
+            if sinks.is_empty() {
+                sinks = DEFAULT_SINKS.iter().map(ToString::to_string).collect();
+            }
+            let report = analyze(&file)?;
+            let findings = report.tainted(&sources, &sinks);
+            for finding in findings.iter() {
+                println!("{}", finding);
+            }
+            // Fail so that tainted effects can be caught in CI
+            if !findings.is_empty() {
+                exit_code = EXIT_TAINTED;
+            }
+            report
+        }
+        Command::Check { file, baseline } => {
+            let baseline_path = baseline_path(&file, baseline);
+            if !baseline_path.exists() {
+                bail!("{}: no baseline, create it with `baseline update`", baseline_path.display());
+            }
+            let baseline = Baseline::read(&baseline_path)?;
+            let report = analyze(&file)?;
+            let changes = report.check(&baseline);
+            print_baseline_changes(&baseline_path, &changes);
+            // Fail so that new side effects are caught in review
+            if changes.iter().any(BaselineChange::fails) {
+                exit_code = EXIT_NOT_IN_BASELINE;
+            }
+            report
+        }
+        Command::Baseline { command: BaselineCommand::Update { file, baseline } } => {
+            let baseline_path = baseline_path(&file, baseline);
+            let report = analyze(&file)?;
+            let baseline = report.baseline();
+            baseline.write(&baseline_path)?;
+            println!("Wrote {} side effects to {}", baseline.side_effects.len(), baseline_path.display());
+            report
+        }
+        Command::Generate { file, policy_engine, exec_transition, exec_once } => {
+            let policy_engine = match policy_engine.or(config.generate.policy_engine) {
+                Some(policy_engine) => policy_engine,
+                None => bail!("--policy-engine is needed when autobox.toml has no `generate.policy_engine`"),
+            };
+            let options = PolicyOptions {
+                exec_transition: exec_transition.or(config.generate.apparmor.exec_transition).unwrap_or_default(),
+                exec_once: exec_once || config.generate.seccomp.exec_once.unwrap_or(false),
+            };
+            let report = analyze(&file)?;
+            print!("{}", report.policy(policy_engine, options));
+            report
+        }
+        Command::Workspace { manifest_path, bins } => {
+            // A binary that can't be read or analyzed is reported, and the others are still analyzed
+            let mut analyzed = 0;
+            let mut failed = 0;
+            for binary in workspace_binaries(manifest_path.as_deref())? {
+                if !bins.is_empty() && !bins.contains(&binary.name) {
+                    continue;
+                }
+                match binary.has_entrypoint() {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(error) => {
+                        eprintln!("error: binary `{}` of {}: {}", binary.name, binary.package, error);
+                        failed += 1;
+                        continue;
+                    }
+                }
+                if analyzed > 0 {
+                    println!();
+                }
+                analyzed += 1;
+                println!("Binary `{}` of {} ({}):", binary.name, binary.package, binary.src_path.display());
+                let report = load_config(cli.config.as_deref(), &binary.package_dir)
+                    .and_then(|config| run_analyzer(binary.analyzer().config(&config)));
+                let report = match report {
+                    Ok(report) => report,
+                    Err(error) => {
+                        eprintln!("error: binary `{}` of {}: {}", binary.name, binary.package, error);
+                        failed += 1;
+                        continue;
+                    }
+                };
+                print_side_effects(&report);
+                print_env_vars(&report);
+                print_gaps(&report, cli.explain_gaps);
+                if !report.is_sound() {
+                    exit_code = EXIT_UNSOUND;
+                }
+            }
+            if analyzed == 0 && failed == 0 {
+                bail!("no binary to analyze has a function with #[effect::entrypoint]");
+            }
+            if failed > 0 {
+                exit_code = EXIT_FAILED;
+            }
+            return Ok(exit_code);
+        }
+        Command::Init { dir, force } => {
+            let path = dir.join(DEFAULT_CONFIG);
+            if path.exists() && !force {
+                bail!("{} already exists, replace it with --force", path.display());
+            }
+            std::fs::write(&path, CONFIG_TEMPLATE)?;
+            println!("Wrote {}", path.display());
+            return Ok(0);
+        }
+    };
+
+    print_gaps(&report, cli.explain_gaps);
+    if exit_code == 0 && !report.is_sound() {
+        exit_code = EXIT_UNSOUND;
+    }
+    Ok(exit_code)
+}
+
+// What the analysis couldn't model, after everything else so it isn't missed. The policy is still
+// printed, but it may be missing rules
+fn print_gaps(report: &AnalysisReport, explain: bool) {
+    if report.is_sound() {
+        return;
+    }
+    if !explain {
+        eprintln!("The analysis has gaps, side effects may be missing (--explain-gaps lists them):");
+        for (category, gaps) in report.gaps_by_category() {
+            eprintln!("    {} {}", gaps.len(), category);
+        }
+        return;
+    }
+    eprintln!("Gaps in the analysis, side effects may be missing:");
+    for (category, gaps) in report.gaps_by_category() {
+        eprintln!("  {}:", category);
+        for gap in gaps {
+            eprintln!("    {}:{}", report.file(&gap.file).display(), gap);
+        }
+    }
+}
+
+// What changed since the baseline as a diff, with broadened effects as the baseline's line replaced
+fn print_baseline_changes(baseline_path: &Path, changes: &[BaselineChange]) {
+    let line = |effect: &BaselineEffect| format!("{} in {}", effect, effect.call_path.join(" -> "));
+    let failing: Vec<_> = changes.iter().filter(|change| change.fails()).collect();
+    if !failing.is_empty() {
+        println!("Side effects that {} doesn't cover:", baseline_path.display());
+        for change in failing {
+            match change {
+                BaselineChange::Added(effect) => println!("+ {}", line(effect)),
+                BaselineChange::Broadened { baseline, current } => {
+                    println!("- {}", line(baseline));
+                    println!("+ {}", line(current));
+                }
+                BaselineChange::Removed(_) => {}
+            }
+        }
+        println!("Run `baseline update` to accept them.");
+    }
+    let removed: Vec<_> = changes.iter()
+        .filter_map(|change| match change {
+            BaselineChange::Removed(effect) => Some(effect),
+            _ => None,
+        })
+        .collect();
+    if !removed.is_empty() {
+        println!("Side effects in {} that weren't found, `baseline update` removes them:", baseline_path.display());
+        for effect in removed {
+            println!("- {}", line(effect));
+        }
+    }
+}
+
+fn print_env_vars(report: &AnalysisReport) {
+    let names = report.env_vars_read();
+    if !names.is_empty() {
+        println!("Environment variables read: {}", names.join(", "));
+    }
+}
+
+// Where each hole in an argument came from, with the file for unsupported syntax
+fn print_hole_origins<'a>(report: &AnalysisReport, origins: impl IntoIterator<Item = &'a HoleOrigin>, indent: &str) {
+    for origin in origins {
+        match origin {
+            HoleOrigin::Unsupported { syntax, line, column } => {
+                println!("{}* from unsupported {} at {}:{}:{}", indent, syntax, report.source.display(), line, column);
+            }
+            origin => println!("{}* from {}", indent, origin),
+        }
+    }
+}
+
+fn print_explanation(report: &AnalysisReport, index: usize, side_effect: &SideEffect) {
+    println!("Side effect {}: {}", index, side_effect);
+    println!("Called through:");
+    for (i, function) in side_effect.call_path.iter().enumerate() {
+        match side_effect.call_sites.get(i) {
+            Some(site) => println!("    {} at {}:{}", function, report.file(&site.file).display(), site),
+            None => println!("    {}", function),
+        }
+    }
+    for (i, argument) in side_effect.arguments.iter().enumerate() {
+        println!("Argument {}: {}", i, argument);
+        if let Some(derivation) = side_effect.derivation(i) {
+            match derivation.bindings.is_empty() {
+                true => println!("    declared as `{}`", derivation.expression),
+                false => println!("    declared as `{}` where", derivation.expression),
+            }
+            for (name, state) in derivation.bindings.iter() {
+                println!("        {} = {}", name, state);
+            }
+        }
+        print_hole_origins(report, argument.hole_origins(), "    ");
+    }
+}
+
+fn print_side_effects(report: &AnalysisReport) {
+    for (index, side_effect) in report.side_effects.iter().enumerate() {
+        let mut notes = Vec::new();
+        if side_effect.repeated {
+            notes.push("possibly repeated");
+        }
+        if side_effect.imprecise {
+            notes.push("imprecise");
+        }
+        if notes.is_empty() {
+            println!("Side effect {}: {}", index, side_effect);
+        } else {
+            println!("Side effect {}: {} ({})", index, side_effect, notes.join(", "));
+        }
+        // Explain every `*`, so it's clear why the effect is broader than it could be
+        print_hole_origins(report, side_effect.hole_origins(), "    ");
+        // Classes that are too broad for a glob are still worth knowing about
+        for class in side_effect.broad_classes() {
+            println!("    * matches {}", class);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_assignments() {
+        assert_eq!(parse_env_assignment("HOME=/home/svc=1"), Ok(("HOME".to_string(), "/home/svc=1".to_string())));
+        assert!(parse_env_assignment("=x").is_err() && parse_env_assignment("HOME").is_err());
+    }
+
+    #[test]
+    fn config_next_to_the_analyzed_file() {
+        let dir = std::env::temp_dir().join(format!("autobox-cli-config-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join(DEFAULT_CONFIG), "entrypoints = [\"serve\"]\n").unwrap();
+        let config = load_config(None, &dir.join("src/main.rs")).unwrap();
+        assert_eq!(config.entrypoints, vec!["serve".to_string()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
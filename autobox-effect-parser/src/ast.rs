@@ -1,14 +1,15 @@
 use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
 use nom::{error::ParseError, IResult};
 use nom::branch::alt;
-use nom::bytes::complete::{
-    tag, take, take_till1,
-};
-use nom::character::complete::{alpha1, alphanumeric1, multispace0};
+use nom::bytes::complete::{tag, take_till1};
+use nom::character::complete::{alpha1, alphanumeric1, digit1, multispace0};
 use nom::combinator::{map_res, opt, recognize};
-use nom::multi::{many0_count, separated_list0};
+use nom::multi::{many0_count, separated_list0, separated_list1};
 use nom::sequence::{delimited, pair, preceded, tuple};
 
+use crate::registry::{EffectKind, Param, ParamKind, Repetition};
+
 // Stolen straight from nom, identifiers are the same as in rust
 pub fn identifier(input: &str) -> IResult<&str, &str> {
     recognize(pair(
@@ -18,11 +19,11 @@ pub fn identifier(input: &str) -> IResult<&str, &str> {
 }
 
 // Remove whitespace from the beginning and end of a string
-fn ws<'a, F: 'a, O, E: ParseError<&'a str>>(
+pub(crate) fn ws<'a, F, O, E: ParseError<&'a str>>(
     inner: F,
 ) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
 where
-    F: Fn(&'a str) -> IResult<&'a str, O, E>,
+    F: Fn(&'a str) -> IResult<&'a str, O, E> + 'a,
 {
     delimited(multispace0, inner, multispace0)
 }
@@ -31,30 +32,180 @@ where
 pub struct Arg<'a> {
     pub arg_name: Cow<'a, str>,
     pub arg_binding: &'a str,
+    /// What the values of the argument look like, ie: `/[0-9]+/` in `id as I: /[0-9]+/`
+    pub pattern: Option<Pattern>,
 }
 
 impl<'a> Arg<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, arg_name) = ws(identifier)(input)?;
         let (input, arg_binding) = preceded(ws(tag("as")), identifier)(input)?;
+        let (input, pattern) = opt(preceded(ws(tag(":")), ws(Pattern::parse)))(input)?;
 
         Ok((
             input,
             Self {
                 arg_name: Cow::Borrowed(arg_name),
                 arg_binding,
+                pattern,
             },
         ))
     }
 }
 
+/// A regular expression restricting the values of an argument, ie: `/[0-9]+/`. Only sequences of
+/// characters and classes with quantifiers are supported, there are no groups or `|`.
+/// Whitespace is ignored, since the tokens of the macro are spaced out when turned into a string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub atoms: Vec<PatternAtom>,
+}
+
+impl Pattern {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (mut input, _) = tag("/")(input)?;
+        let mut atoms = Vec::new();
+        loop {
+            match next_char(input) {
+                Some(('/', rest)) => return Ok((rest, Self { atoms })),
+                Some(_) => {
+                    let (rest, atom) = PatternAtom::parse(input)?;
+                    atoms.push(atom);
+                    input = rest;
+                }
+                None => return Err(pattern_error(input)),
+            }
+        }
+    }
+}
+
+/// A character class repeated between `min` and `max` times, ie: `[0-9]+` or `a{2}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternAtom {
+    pub class: CharClass,
+    pub min: usize,
+    /// `None` when unbounded, ie: for `+` and `*`
+    pub max: Option<usize>,
+}
+
+impl PatternAtom {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, class) = CharClass::parse(input)?;
+        let (input, (min, max)) = match next_char(input) {
+            Some(('+', rest)) => (rest, (1, None)),
+            Some(('*', rest)) => (rest, (0, None)),
+            Some(('?', rest)) => (rest, (0, Some(1))),
+            Some(('{', rest)) => {
+                let (rest, min) = ws(number)(rest)?;
+                let (rest, max) = match next_char(rest) {
+                    Some((',', rest)) => {
+                        let (rest, max) = opt(ws(number))(rest)?;
+                        (rest, max)
+                    }
+                    _ => (rest, Some(min)),
+                };
+                let (rest, _) = ws(tag("}"))(rest)?;
+                (rest, (min, max))
+            }
+            _ => (input, (1, Some(1))),
+        };
+        Ok((input, Self { class, min, max }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharClass {
+    /// `a`, or an escaped character like `\.`
+    Literal(char),
+    /// `.`
+    Any,
+    /// `[a-z_]`, `[^/]` or `\d`
+    Set {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+impl CharClass {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        match next_char(input) {
+            Some(('.', rest)) => Ok((rest, CharClass::Any)),
+            Some(('\\', rest)) => escaped(rest),
+            Some(('[', rest)) => {
+                let (mut rest, negated) = match next_char(rest) {
+                    Some(('^', rest)) => (rest, true),
+                    _ => (rest, false),
+                };
+                let mut ranges = Vec::new();
+                loop {
+                    let (after, start) = match next_char(rest) {
+                        Some((']', after)) => return Ok((after, CharClass::Set { ranges, negated })),
+                        Some(('\\', after)) => match escaped(after)? {
+                            (after, CharClass::Literal(c)) => (after, c),
+                            (after, CharClass::Set { ranges: escaped_ranges, .. }) => {
+                                ranges.extend(escaped_ranges);
+                                rest = after;
+                                continue;
+                            }
+                            _ => return Err(pattern_error(after)),
+                        },
+                        Some((c, after)) => (after, c),
+                        None => return Err(pattern_error(rest)),
+                    };
+                    // `a-z`, but a `-` right before the `]` is just a `-`
+                    rest = match next_char(after) {
+                        Some(('-', range_end)) if !matches!(next_char(range_end), Some((']', _))) => {
+                            let (end, after_end) = next_char(range_end).ok_or_else(|| pattern_error(range_end))?;
+                            ranges.push((start, end));
+                            after_end
+                        }
+                        _ => {
+                            ranges.push((start, start));
+                            after
+                        }
+                    };
+                }
+            }
+            Some(('+' | '*' | '?' | '{' | '/', _)) | None => Err(pattern_error(input)),
+            Some((c, rest)) => Ok((rest, CharClass::Literal(c))),
+        }
+    }
+}
+
+// The class of an escape sequence, after the `\\`
+fn escaped(input: &str) -> IResult<&str, CharClass> {
+    let mut chars = input.chars();
+    let class = match chars.next() {
+        Some('d') => CharClass::Set { ranges: vec![('0', '9')], negated: false },
+        Some('w') => CharClass::Set { ranges: vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')], negated: false },
+        Some(c) => CharClass::Literal(c),
+        None => return Err(pattern_error(input)),
+    };
+    Ok((chars.as_str(), class))
+}
+
+fn number(input: &str) -> IResult<&str, usize> {
+    map_res(digit1, str::parse)(input)
+}
+
+// The next character of a pattern that isn't whitespace
+fn next_char(input: &str) -> Option<(char, &str)> {
+    let input = input.trim_start();
+    let mut chars = input.chars();
+    chars.next().map(|c| (c, chars.as_str()))
+}
+
+fn pattern_error(input: &str) -> nom::Err<nom::error::Error<&str>> {
+    nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Char))
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Args<'a> {
     pub args: Vec<Arg<'a>>,
 }
 
 impl<'a> Args<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, args) = delimited(
             ws(tag("(")),
             separated_list0(ws(tag(",")), Arg::parse),
@@ -64,19 +215,38 @@ impl<'a> Args<'a> {
     }
 }
 
+/// Concatenation of strings, or the sum of two integers
 #[derive(Debug, Clone)]
 pub struct Add<'a> {
     pub lhs: Expr<'a>,
     pub rhs: Expr<'a>,
 }
 
-impl<'a> Add<'a> {
-    fn parse(input: &'a str) -> IResult<&str, Self> {
-        let (input, lhs) = ws(take_till1(|s| s == '+' || s == ')'))(input)?;
-        let _ = take(1usize)(input)?.1;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Sub,
+    Mul,
+}
+
+/// Integer arithmetic other than `+`, ie: `PORT - 1` or `BASE * 2`
+#[derive(Debug, Clone)]
+pub struct Arithmetic<'a> {
+    pub op: ArithmeticOp,
+    pub lhs: Expr<'a>,
+    pub rhs: Expr<'a>,
+}
+
+/// Either of two values, ie: `'/etc/app.toml' | HOME + '/.app.toml'`. Binds looser than `+`
+#[derive(Debug, Clone)]
+pub struct Union<'a> {
+    pub lhs: Expr<'a>,
+    pub rhs: Expr<'a>,
+}
 
-        let (_, lhs) = ws(Expr::parse)(lhs)?;
-        let (input, _) = take(1usize)(input)?;
+impl<'a> Union<'a> {
+    fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, lhs) = ws(Expr::parse_sum)(input)?;
+        let (input, _) = ws(tag("|"))(input)?;
         let (input, rhs) = ws(Expr::parse)(input)?;
         Ok((input, Self { lhs, rhs }))
     }
@@ -88,7 +258,7 @@ pub struct LitStr<'a> {
 }
 
 impl<'a> LitStr<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, value) = alt((
             delimited(tag("'"), take_till1(|c| c == '\''), tag("'")),
             delimited(tag("\""), take_till1(|c| c == '"'), tag("\"")),
@@ -97,13 +267,41 @@ impl<'a> LitStr<'a> {
     }
 }
 
+/// An integer, ie: the `8080` in `(HOST, 8080)`
+#[derive(Debug, Clone)]
+pub struct LitInt {
+    pub value: i128,
+}
+
+impl LitInt {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, value) = map_res(digit1, str::parse)(input)?;
+        Ok((input, Self { value }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LitBool {
+    pub value: bool,
+}
+
+impl LitBool {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        map_res(identifier, |ident| match ident {
+            "true" => Ok(Self { value: true }),
+            "false" => Ok(Self { value: false }),
+            _ => Err(()),
+        })(input)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Var<'a> {
     pub name: &'a str,
 }
 
 impl<'a> Var<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, name) = identifier(input)?;
         Ok((input, Self { name }))
     }
@@ -112,8 +310,14 @@ impl<'a> Var<'a> {
 #[derive(Debug, Clone)]
 pub enum Expr<'a> {
     LitStr(LitStr<'a>),
+    LitInt(LitInt),
+    LitBool(LitBool),
     Var(Var<'a>),
     Add(Box<Add<'a>>),
+    Arithmetic(Box<Arithmetic<'a>>),
+    Union(Box<Union<'a>>),
+    /// `(HOST, PORT)`, which is also how socket addresses are written
+    Tuple(Vec<Expr<'a>>),
 }
 
 impl<'a> Expr<'a> {
@@ -126,7 +330,23 @@ impl<'a> Expr<'a> {
     }
 
     #[track_caller]
-    pub fn unwrap_var(&self) -> &Var {
+    pub fn unwrap_lit_int(&self) -> &LitInt {
+        match self {
+            Expr::LitInt(lit_int) => lit_int,
+            _ => panic!("Expected LitInt"),
+        }
+    }
+
+    #[track_caller]
+    pub fn unwrap_lit_bool(&self) -> &LitBool {
+        match self {
+            Expr::LitBool(lit_bool) => lit_bool,
+            _ => panic!("Expected LitBool"),
+        }
+    }
+
+    #[track_caller]
+    pub fn unwrap_var(&self) -> &Var<'a> {
         match self {
             Expr::Var(var) => var,
             _ => panic!("Expected Var"),
@@ -140,19 +360,152 @@ impl<'a> Expr<'a> {
             _ => panic!("Expected Add"),
         }
     }
+
+    #[track_caller]
+    pub fn unwrap_arithmetic(&self) -> &Arithmetic<'a> {
+        match self {
+            Expr::Arithmetic(arithmetic) => arithmetic,
+            _ => panic!("Expected Arithmetic"),
+        }
+    }
+
+    #[track_caller]
+    pub fn unwrap_tuple(&self) -> &[Expr<'a>] {
+        match self {
+            Expr::Tuple(fields) => fields,
+            _ => panic!("Expected Tuple"),
+        }
+    }
+
+    #[track_caller]
+    pub fn unwrap_union(&self) -> &Union<'a> {
+        match self {
+            Expr::Union(union) => union,
+            _ => panic!("Expected Union"),
+        }
+    }
 }
 
 impl<'a> Expr<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
-        let (input, expr) = alt((
-            map_res(ws(Add::parse), |add| {
-                Ok::<Expr<'_>, &str>(Expr::Add(Box::new(add)))
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
+            map_res(ws(Union::parse), |union| {
+                Ok::<Expr<'_>, &str>(Expr::Union(Box::new(union)))
             }),
+            Self::parse_sum,
+        ))(input)
+    }
+
+    // An expression without a `|`. `+` and `-` are left associative and bind looser than `*`
+    fn parse_sum(input: &'a str) -> IResult<&'a str, Self> {
+        let (mut input, mut expr) = Self::parse_product(input)?;
+        loop {
+            let (rest, op) = match opt(alt((ws(tag("+")), ws(tag("-")))))(input)? {
+                (rest, Some(op)) => (rest, op),
+                (_, None) => return Ok((input, expr)),
+            };
+            let (rest, rhs) = Self::parse_product(rest)?;
+            expr = match op {
+                "+" => Expr::Add(Box::new(Add { lhs: expr, rhs })),
+                _ => Expr::Arithmetic(Box::new(Arithmetic { op: ArithmeticOp::Sub, lhs: expr, rhs })),
+            };
+            input = rest;
+        }
+    }
+
+    fn parse_product(input: &'a str) -> IResult<&'a str, Self> {
+        let (mut input, mut expr) = Self::parse_term(input)?;
+        while let (rest, Some(_)) = opt(ws(tag("*")))(input)? {
+            let (rest, rhs) = Self::parse_term(rest)?;
+            expr = Expr::Arithmetic(Box::new(Arithmetic { op: ArithmeticOp::Mul, lhs: expr, rhs }));
+            input = rest;
+        }
+        Ok((input, expr))
+    }
+
+    // A single literal, variable, tuple or parenthesized expression, the operands of a binary expression
+    fn parse_term(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
             map_res(ws(LitStr::parse), |s| Ok::<Expr<'_>, &str>(Expr::LitStr(s))),
+            map_res(ws(LitInt::parse), |i| Ok::<Expr<'_>, &str>(Expr::LitInt(i))),
+            map_res(ws(LitBool::parse), |b| Ok::<Expr<'_>, &str>(Expr::LitBool(b))),
             map_res(ws(Var::parse), |var| Ok::<Expr<'_>, &str>(Expr::Var(var))),
-        ))(input)?;
+            map_res(
+                delimited(ws(tag("(")), separated_list0(ws(tag(",")), Self::parse), ws(tag(")"))),
+                |mut fields| match fields.len() {
+                    0 => Err("empty tuple"),
+                    1 => Ok(fields.remove(0)),
+                    _ => Ok(Expr::Tuple(fields)),
+                },
+            ),
+        ))(input)
+    }
 
-        Ok((input, expr))
+    /// The names of every variable in the expression, ie: `P` and `EXT` in `P + '.' + EXT`
+    pub fn variables(&self) -> Vec<&'a str> {
+        match self {
+            Expr::LitStr(_) | Expr::LitInt(_) | Expr::LitBool(_) => Vec::new(),
+            Expr::Var(var) => vec![var.name],
+            Expr::Add(add) => [add.lhs.variables(), add.rhs.variables()].concat(),
+            Expr::Arithmetic(arithmetic) => [arithmetic.lhs.variables(), arithmetic.rhs.variables()].concat(),
+            Expr::Union(union) => [union.lhs.variables(), union.rhs.variables()].concat(),
+            Expr::Tuple(fields) => fields.iter().flat_map(Expr::variables).collect(),
+        }
+    }
+
+    // How tightly the expression binds, so operands that bind looser can be parenthesized
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Union(_) => 0,
+            Expr::Add(_) => 1,
+            Expr::Arithmetic(arithmetic) if arithmetic.op == ArithmeticOp::Sub => 1,
+            Expr::Arithmetic(_) => 2,
+            _ => 3,
+        }
+    }
+}
+
+impl Display for Expr<'_> {
+    /// As it would be written in a declaration, ie: `A + '/' + B`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // `+`, `-` and `*` are left associative, so a right operand that binds as loosely needs parentheses
+        let operands = |f: &mut Formatter<'_>, lhs: &Expr<'_>, op: &str, rhs: &Expr<'_>| {
+            match lhs.precedence() < self.precedence() {
+                true => write!(f, "({})", lhs)?,
+                false => write!(f, "{}", lhs)?,
+            }
+            write!(f, " {} ", op)?;
+            match rhs.precedence() <= self.precedence() && self.precedence() > 0 {
+                true => write!(f, "({})", rhs),
+                false => write!(f, "{}", rhs),
+            }
+        };
+        match self {
+            Expr::LitStr(s) if s.value.contains('\'') => write!(f, "\"{}\"", s.value),
+            Expr::LitStr(s) => write!(f, "'{}'", s.value),
+            Expr::LitInt(i) => write!(f, "{}", i.value),
+            Expr::LitBool(b) => write!(f, "{}", b.value),
+            Expr::Var(var) => write!(f, "{}", var.name),
+            Expr::Add(add) => operands(f, &add.lhs, "+", &add.rhs),
+            Expr::Arithmetic(arithmetic) => {
+                let op = match arithmetic.op {
+                    ArithmeticOp::Sub => "-",
+                    ArithmeticOp::Mul => "*",
+                };
+                operands(f, &arithmetic.lhs, op, &arithmetic.rhs)
+            }
+            Expr::Union(union) => operands(f, &union.lhs, "|", &union.rhs),
+            Expr::Tuple(fields) => {
+                write!(f, "(")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", field)?;
+                }
+                write!(f, ")")
+            }
+        }
     }
 }
 
@@ -164,10 +517,13 @@ pub struct SideEffectStmt<'a> {
 }
 
 impl<'a> SideEffectStmt<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, side_effect_name) = ws(identifier)(input)?;
-        let (input, args) = delimited(ws(tag("(")), take_till1(|c| c == ')'), ws(tag(")")))(input)?;
-        let (_input, side_effect_arguments) = separated_list0(ws(tag(",")), ws(Expr::parse))(args)?;
+        let (input, side_effect_arguments) = delimited(
+            ws(tag("(")),
+            separated_list0(ws(tag(",")), ws(Expr::parse)),
+            ws(tag(")")),
+        )(input)?;
 
         let (input, binding) = opt(preceded(ws(tag("as")), identifier))(input)?;
         Ok((
@@ -187,7 +543,7 @@ pub struct SideEffects<'a> {
 }
 
 impl<'a> SideEffects<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, _) = ws(tag("("))(input)?;
         let (input, side_effect_stmts) =
             separated_list0(ws(tag(",")), SideEffectStmt::parse)(input)?;
@@ -197,20 +553,53 @@ impl<'a> SideEffects<'a> {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Mutation<'a> {
+    pub arg_name: &'a str,
+    pub expr: Expr<'a>,
+}
+
+impl<'a> Mutation<'a> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, arg_name) = ws(identifier)(input)?;
+        let (input, _) = ws(tag("="))(input)?;
+        let (input, expr) = ws(Expr::parse)(input)?;
+        Ok((input, Self { arg_name, expr }))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mutates<'a> {
+    pub mutations: Vec<Mutation<'a>>,
+}
+
+impl<'a> Mutates<'a> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, _) = ws(tag("("))(input)?;
+        let (input, mutations) = separated_list0(ws(tag(",")), Mutation::parse)(input)?;
+        let (input, _) = ws(tag(")"))(input)?;
+        let (input, _) = opt(ws(tag(",")))(input)?;
+        Ok((input, Self { mutations }))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DeclareMacro<'a> {
     pub args: Args<'a>,
     pub side_effects: Option<SideEffects<'a>>,
+    /// New states of `&mut` arguments after the call, ie: `mutates=(p = P + '.json')`
+    pub mutates: Option<Mutates<'a>>,
     pub returns: Option<Expr<'a>>,
     // todo: strictly require function calls to be to declared, inferred, or pure functions
     // pub require_inner_calls_marked: bool,
 }
 
 impl<'a> DeclareMacro<'a> {
-    pub fn parse(input: &'a str) -> IResult<&str, Self> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
         let (input, _) = opt(ws(tag("(")))(input)?;
         let (input, args) = opt(delimited(preceded(ws(tag("args")), ws(tag("="))), Args::parse, ws(tag(","))))(input)?;
         let (input, side_effects) = opt(preceded(preceded(ws(tag("side_effects")), ws(tag("="))), SideEffects::parse))(input)?;
+        let (input, mutates) = opt(preceded(preceded(ws(tag("mutates")), ws(tag("="))), Mutates::parse))(input)?;
 
         let (input, returns) = opt(preceded(preceded(ws(tag("returns")), ws(tag("="))), tuple((opt(ws(tag("("))), Expr::parse, opt(ws(tag(")")))))))(input)?;
         let returns = returns.map(|(_, expr, _)| expr);
@@ -220,12 +609,75 @@ impl<'a> DeclareMacro<'a> {
             Self {
                 args: args.unwrap_or_default(),
                 side_effects,
+                mutates,
                 returns,
             },
         ))
     }
 }
 
+/// A parameter of a defined effect, ie: `p: path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionParam<'a> {
+    pub name: &'a str,
+    pub kind: ParamKind,
+}
+
+impl<'a> DefinitionParam<'a> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, name) = ws(identifier)(input)?;
+        let (input, kind) = preceded(ws(tag(":")), ws(ParamKind::parse))(input)?;
+        Ok((input, Self { name, kind }))
+    }
+}
+
+/// An effect made of other effects, from `effect::define!`, ie:
+/// `reads_config(p: path) => reads_file(p), stat(p)`
+#[derive(Debug, Clone)]
+pub struct EffectDefinition<'a> {
+    pub name: &'a str,
+    pub params: Vec<DefinitionParam<'a>>,
+    /// The effects it expands into, whose arguments are expressions of the parameters
+    pub expansion: Vec<SideEffectStmt<'a>>,
+}
+
+impl<'a> EffectDefinition<'a> {
+    pub fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, name) = ws(identifier)(input)?;
+        let (input, params) = delimited(
+            ws(tag("(")),
+            separated_list0(ws(tag(",")), DefinitionParam::parse),
+            ws(tag(")")),
+        )(input)?;
+        let (input, _) = ws(tag("=>"))(input)?;
+        let (input, expansion) = separated_list1(ws(tag(",")), SideEffectStmt::parse)(input)?;
+        let (input, _) = opt(ws(tag(",")))(input)?;
+        Ok((input, Self { name, params, expansion }))
+    }
+
+    /// The effect as it appears in a registry, without any sandbox mappings of its own
+    pub fn effect_kind(&self) -> EffectKind {
+        EffectKind {
+            name: self.name.to_string(),
+            params: self.params.iter().map(|param| Param { kind: param.kind, repetition: Repetition::Once }).collect(),
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Variables used in the expansion that aren't parameters
+    pub fn unknown_variables(&self) -> Vec<&'a str> {
+        let mut unknown = Vec::new();
+        for stmt in self.expansion.iter() {
+            for variable in stmt.side_effect_arguments.iter().flat_map(Expr::variables) {
+                if !self.params.iter().any(|param| param.name == variable) && !unknown.contains(&variable) {
+                    unknown.push(variable);
+                }
+            }
+        }
+        unknown
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,6 +690,48 @@ mod tests {
         assert_eq!(rest, "");
     }
 
+    #[test]
+    fn test_arg_pattern_parse() {
+        let (rest, arg) = Arg::parse("id as I: /[0-9]+/").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(arg.arg_binding, "I");
+        assert_eq!(arg.pattern.unwrap().atoms, vec![PatternAtom {
+            class: CharClass::Set { ranges: vec![('0', '9')], negated: false },
+            min: 1,
+            max: None,
+        }]);
+
+        // As it looks once the macro's tokens are turned into a string
+        let (rest, args) = Args::parse("(name as N : / [^ /] {1 , 8} . json /, p as P)").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(args.args.len(), 2);
+        let atoms = &args.args[0].pattern.as_ref().unwrap().atoms;
+        assert_eq!(atoms.len(), 6);
+        assert_eq!(atoms[0], PatternAtom {
+            class: CharClass::Set { ranges: vec![('/', '/')], negated: true },
+            min: 1,
+            max: Some(8),
+        });
+        assert_eq!(atoms[1].class, CharClass::Any);
+        assert_eq!(atoms[2].class, CharClass::Literal('j'));
+        assert!(args.args[1].pattern.is_none());
+    }
+
+    #[test]
+    fn test_pattern_parse() {
+        let (rest, pattern) = Pattern::parse(r"/v\.\d{2,}[a-f-]?x*/").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(pattern.atoms, vec![
+            PatternAtom { class: CharClass::Literal('v'), min: 1, max: Some(1) },
+            PatternAtom { class: CharClass::Literal('.'), min: 1, max: Some(1) },
+            PatternAtom { class: CharClass::Set { ranges: vec![('0', '9')], negated: false }, min: 2, max: None },
+            PatternAtom { class: CharClass::Set { ranges: vec![('a', 'f'), ('-', '-')], negated: false }, min: 0, max: Some(1) },
+            PatternAtom { class: CharClass::Literal('x'), min: 0, max: None },
+        ]);
+        assert!(Pattern::parse("/+/").is_err());
+        assert!(Pattern::parse("/[0-9/").is_err());
+    }
+
     #[test]
     fn test_args_parse() {
         let (rest, args) = Args::parse("(foo as bar, baz as qux)").unwrap();
@@ -484,10 +978,135 @@ mod tests {
     fn test_expr_add_nested_parse() {
         let (rest, expr) = Expr::parse("'foo' + bar + baz").unwrap();
         let add_op = expr.unwrap_add();
-        let rhs = add_op.rhs.unwrap_add();
-        assert_eq!(add_op.lhs.unwrap_lit_str().value, "foo");
-        assert_eq!(rhs.lhs.unwrap_var().name, "bar");
-        assert_eq!(rhs.rhs.unwrap_var().name, "baz");
+        let lhs = add_op.lhs.unwrap_add();
+        assert_eq!(lhs.lhs.unwrap_lit_str().value, "foo");
+        assert_eq!(lhs.rhs.unwrap_var().name, "bar");
+        assert_eq!(add_op.rhs.unwrap_var().name, "baz");
+        assert_eq!(rest, "");
+    }
+
+    #[test]
+    fn test_expr_union_parse() {
+        let (rest, expr) = Expr::parse("'/etc/app.toml' | HOME + '/.app.toml' | P").unwrap();
+        assert_eq!(rest, "");
+        let union = expr.unwrap_union();
+        assert_eq!(union.lhs.unwrap_lit_str().value, "/etc/app.toml");
+        let rhs = union.rhs.unwrap_union();
+        let add_op = rhs.lhs.unwrap_add();
+        assert_eq!(add_op.lhs.unwrap_var().name, "HOME");
+        assert_eq!(add_op.rhs.unwrap_lit_str().value, "/.app.toml");
+        assert_eq!(rhs.rhs.unwrap_var().name, "P");
+    }
+
+    #[test]
+    fn test_expr_arithmetic_parse() {
+        let (rest, expr) = Expr::parse("PORT - 1 - 2 * (BASE + 3)").unwrap();
         assert_eq!(rest, "");
+        let sub = expr.unwrap_arithmetic();
+        assert_eq!(sub.op, ArithmeticOp::Sub);
+        let inner = sub.lhs.unwrap_arithmetic();
+        assert_eq!(inner.op, ArithmeticOp::Sub);
+        assert_eq!(inner.lhs.unwrap_var().name, "PORT");
+        assert_eq!(inner.rhs.unwrap_lit_int().value, 1);
+        let mul = sub.rhs.unwrap_arithmetic();
+        assert_eq!(mul.op, ArithmeticOp::Mul);
+        assert_eq!(mul.lhs.unwrap_lit_int().value, 2);
+        let add_op = mul.rhs.unwrap_add();
+        assert_eq!(add_op.lhs.unwrap_var().name, "BASE");
+        assert_eq!(add_op.rhs.unwrap_lit_int().value, 3);
+    }
+
+    #[test]
+    fn test_expr_tuple_parse() {
+        let (rest, side_effect_stmt) = SideEffectStmt::parse("binds(('0.0.0.0', PORT + 1), true)").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(side_effect_stmt.side_effect_arguments.len(), 2);
+        let fields = side_effect_stmt.side_effect_arguments[0].unwrap_tuple();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].unwrap_lit_str().value, "0.0.0.0");
+        assert_eq!(fields[1].unwrap_add().lhs.unwrap_var().name, "PORT");
+        assert!(side_effect_stmt.side_effect_arguments[1].unwrap_lit_bool().value);
+
+        let (_, expr) = Expr::parse("trueish").unwrap();
+        assert_eq!(expr.unwrap_var().name, "trueish");
+    }
+
+    #[test]
+    fn test_expr_add_in_list_parse() {
+        let (rest, side_effect_stmt) = SideEffectStmt::parse("eval(T, U + '/')").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(side_effect_stmt.side_effect_arguments.len(), 2);
+        assert_eq!(side_effect_stmt.side_effect_arguments[0].unwrap_var().name, "T");
+        let add_op = side_effect_stmt.side_effect_arguments[1].unwrap_add();
+        assert_eq!(add_op.lhs.unwrap_var().name, "U");
+        assert_eq!(add_op.rhs.unwrap_lit_str().value, "/");
+    }
+
+    #[test]
+    fn test_mutates_parse() {
+        let (rest, mutates) = Mutates::parse(r#"(p = P + ".json", q = Q)"#).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(mutates.mutations.len(), 2);
+        assert_eq!(mutates.mutations[0].arg_name, "p");
+        let add_op = mutates.mutations[0].expr.unwrap_add();
+        assert_eq!(add_op.lhs.unwrap_var().name, "P");
+        assert_eq!(add_op.rhs.unwrap_lit_str().value, ".json");
+        assert_eq!(mutates.mutations[1].arg_name, "q");
+        assert_eq!(mutates.mutations[1].expr.unwrap_var().name, "Q");
+    }
+
+    #[test]
+    fn test_declare_macro_mutates_parse() {
+        let declare_macro = r#"
+            args=(p as P),
+            side_effects=(reads_file(P)),
+            mutates=(p = P + ".json"),
+            returns=(P)
+        "#;
+        let (rest, declare_macro) = DeclareMacro::parse(declare_macro).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(declare_macro.side_effects.as_ref().unwrap().side_effect_stmts.len(), 1);
+        let mutates = declare_macro.mutates.as_ref().unwrap();
+        assert_eq!(mutates.mutations.len(), 1);
+        assert_eq!(mutates.mutations[0].arg_name, "p");
+        assert_eq!(declare_macro.returns.as_ref().unwrap().unwrap_var().name, "P");
+    }
+
+    #[test]
+    fn test_expr_display() {
+        for expr in [
+            "A + '/' + B",
+            "(HOST, PORT + 1)",
+            "'/etc/app.toml' | HOME + '/.app.toml'",
+            "(PORT - 1) * 2",
+            "PORT - (BASE - 1)",
+            "\"it's\"",
+            "true",
+        ] {
+            let (rest, parsed) = Expr::parse(expr).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(parsed.to_string(), expr);
+        }
+    }
+
+    #[test]
+    fn test_effect_definition_parse() {
+        // As it looks once the macro's tokens are turned into a string
+        let (rest, definition) = EffectDefinition::parse("reads_config(p : path, ext : value) => reads_file(p + '.' + ext), stat(p),").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(definition.name, "reads_config");
+        assert_eq!(definition.params, vec![
+            DefinitionParam { name: "p", kind: ParamKind::Path },
+            DefinitionParam { name: "ext", kind: ParamKind::Value },
+        ]);
+        assert_eq!(definition.expansion.len(), 2);
+        assert_eq!(definition.expansion[1].side_effect_name, "stat");
+        assert!(definition.unknown_variables().is_empty());
+        assert_eq!(definition.effect_kind().to_string(), "reads_config(path, value)");
+
+        let (_, definition) = EffectDefinition::parse("opens_db(p: path) => reads_file(P), writes_file(P + '-wal')").unwrap();
+        assert_eq!(definition.unknown_variables(), vec!["P"]);
+        assert!(EffectDefinition::parse("opens_db(p) => reads_file(p)").is_err());
+        assert!(EffectDefinition::parse("opens_db(p: path) =>").is_err());
     }
 }
@@ -0,0 +1,352 @@
+use std::fmt::{Display, Formatter};
+
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{map, map_res, opt, value};
+use nom::multi::{separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded};
+
+use crate::ast::{identifier, ws};
+
+/// The registry every other one extends, with the effects `autobox` knows the meaning of
+pub const BUILTIN_EFFECTS: &str = include_str!("builtin.effects");
+
+/// What a parameter of an effect holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    /// A file or directory, ie: `~/.config/app.toml`
+    Path,
+    /// A hostname or IP address
+    Host,
+    Port,
+    /// Anything `ToSocketAddrs` takes, ie: `("0.0.0.0", 8080)`, or a host followed by a port
+    Address,
+    /// A program to run, either a path or a name looked up in `PATH`
+    Program,
+    /// `"tcp"` or `"udp"`
+    Protocol,
+    /// The name of something, ie: an environment variable
+    Name,
+    /// Any value
+    Value,
+}
+
+impl ParamKind {
+    const NAMES: &'static [(&'static str, ParamKind)] = &[
+        ("path", ParamKind::Path),
+        ("host", ParamKind::Host),
+        ("port", ParamKind::Port),
+        ("address", ParamKind::Address),
+        ("program", ParamKind::Program),
+        ("protocol", ParamKind::Protocol),
+        ("name", ParamKind::Name),
+        ("value", ParamKind::Value),
+    ];
+
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        map_res(identifier, |name| {
+            Self::NAMES.iter().find(|(n, _)| *n == name).map(|(_, kind)| *kind).ok_or(())
+        })(input)
+    }
+}
+
+impl Display for ParamKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (name, _) = Self::NAMES.iter().find(|(_, kind)| kind == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repetition {
+    Once,
+    /// `kind?`
+    Optional,
+    /// `kind..`, any number of values, which must be the last parameter
+    Variadic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param {
+    pub kind: ParamKind,
+    pub repetition: Repetition,
+}
+
+impl Param {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, kind) = ws(ParamKind::parse)(input)?;
+        let (input, repetition) = opt(alt((
+            value(Repetition::Optional, ws(tag("?"))),
+            value(Repetition::Variadic, ws(tag(".."))),
+        )))(input)?;
+        Ok((input, Self { kind, repetition: repetition.unwrap_or(Repetition::Once) }))
+    }
+}
+
+/// What an effect means to a sandbox, so a policy can allow it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxMapping {
+    /// `file(rw)`, access to the `path` parameter with the AppArmor permissions given
+    File(String),
+    /// `network(connect)`, one of `connect`, `bind`, `listen`, `send_to` or `resolve`
+    Network(String),
+    /// `exec`, running the `program` parameter
+    Exec,
+}
+
+impl SandboxMapping {
+    const NETWORK_KINDS: &'static [&'static str] = &["connect", "bind", "listen", "send_to", "resolve"];
+
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let permissions = map_res(identifier, |permissions: &str| {
+            match !permissions.is_empty() && permissions.chars().all(|c| "rwlk".contains(c)) {
+                true => Ok(permissions.to_string()),
+                false => Err(()),
+            }
+        });
+        let network_kind = map_res(identifier, |kind| match Self::NETWORK_KINDS.contains(&kind) {
+            true => Ok(kind.to_string()),
+            false => Err(()),
+        });
+        alt((
+            map(preceded(ws(tag("file")), delimited(ws(tag("(")), permissions, ws(tag(")")))), Self::File),
+            map(preceded(ws(tag("network")), delimited(ws(tag("(")), network_kind, ws(tag(")")))), Self::Network),
+            value(Self::Exec, ws(tag("exec"))),
+        ))(input)
+    }
+}
+
+impl Display for SandboxMapping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxMapping::File(permissions) => write!(f, "file({})", permissions),
+            SandboxMapping::Network(kind) => write!(f, "network({})", kind),
+            SandboxMapping::Exec => write!(f, "exec"),
+        }
+    }
+}
+
+/// A known effect, ie: `connect(address, port?, protocol?) => network(connect)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectKind {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub mappings: Vec<SandboxMapping>,
+}
+
+impl EffectKind {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, name) = ws(identifier)(input)?;
+        let (input, params) = delimited(
+            ws(tag("(")),
+            separated_list0(ws(tag(",")), Param::parse),
+            ws(tag(")")),
+        )(input)?;
+        let (input, mappings) = opt(preceded(
+            ws(tag("=>")),
+            separated_list1(ws(tag(",")), ws(SandboxMapping::parse)),
+        ))(input)?;
+        Ok((input, Self {
+            name: name.to_string(),
+            params,
+            mappings: mappings.unwrap_or_default(),
+        }))
+    }
+
+    /// The fewest arguments the effect takes, and the most unless it's variadic
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        let min = self.params.iter().filter(|param| param.repetition == Repetition::Once).count();
+        match self.params.iter().any(|param| param.repetition == Repetition::Variadic) {
+            true => (min, None),
+            false => (min, Some(self.params.len())),
+        }
+    }
+
+    /// The index of the first parameter of `kind`
+    pub fn param_index(&self, kind: ParamKind) -> Option<usize> {
+        self.params.iter().position(|param| param.kind == kind)
+    }
+}
+
+impl Display for EffectKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", param.kind)?;
+            match param.repetition {
+                Repetition::Once => {}
+                Repetition::Optional => write!(f, "?")?,
+                Repetition::Variadic => write!(f, "..")?,
+            }
+        }
+        write!(f, ")")?;
+        for (i, mapping) in self.mappings.iter().enumerate() {
+            match i {
+                0 => write!(f, " => {}", mapping)?,
+                _ => write!(f, ", {}", mapping)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every known effect, one per line of a registry file. Lines starting with `#` are comments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EffectRegistry {
+    pub effects: Vec<EffectKind>,
+}
+
+impl EffectRegistry {
+    /// Parse a registry file, failing with the line number of the first effect that can't be parsed
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut registry = Self::default();
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match EffectKind::parse(line) {
+                Ok(("", effect)) => registry.add(effect),
+                _ => return Err(format!("line {}: expected an effect like `reads_file(path) => file(r)`, found `{}`", i + 1, line)),
+            }
+        }
+        Ok(registry)
+    }
+
+    /// The effects `autobox` knows the meaning of
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_EFFECTS).unwrap()
+    }
+
+    /// Add an effect, replacing any other with the same name
+    pub fn add(&mut self, effect: EffectKind) {
+        self.effects.retain(|existing| existing.name != effect.name);
+        self.effects.push(effect);
+    }
+
+    /// Add every effect of `other`, replacing those with the same names
+    pub fn extend(&mut self, other: EffectRegistry) {
+        for effect in other.effects {
+            self.add(effect);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EffectKind> {
+        self.effects.iter().find(|effect| effect.name == name)
+    }
+
+    /// The effect `name`, if it means `mapping` to a sandbox
+    pub fn mapped_to(&self, name: &str, mapping: &SandboxMapping) -> Option<&EffectKind> {
+        self.get(name).filter(|effect| effect.mappings.contains(mapping))
+    }
+
+    /// A warning about using the effect `name` with `arity` arguments, when it isn't known or
+    /// takes a different number of arguments
+    pub fn check(&self, name: &str, arity: usize) -> Option<String> {
+        let effect = match self.get(name) {
+            Some(effect) => effect,
+            None => {
+                // A typo is usually a letter or two away, ie: `read_file` for `reads_file`
+                let suggestion = self.effects.iter()
+                    .map(|effect| (edit_distance(name, &effect.name), &effect.name))
+                    .filter(|(distance, _)| *distance <= 2)
+                    .min();
+                return Some(match suggestion {
+                    Some((_, suggestion)) => format!("unknown effect `{}`, did you mean `{}`?", name, suggestion),
+                    None => format!("unknown effect `{}`", name),
+                });
+            }
+        };
+        let (min, max) = effect.arity();
+        if arity >= min && max.is_none_or(|max| arity <= max) {
+            return None;
+        }
+        let expected = match max {
+            Some(1) if min == 1 => "1 argument".to_string(),
+            Some(max) if max == min => format!("{} arguments", min),
+            Some(max) => format!("{} to {} arguments", min, max),
+            None => format!("at least {} arguments", min),
+        };
+        Some(format!("`{}` takes {}, found {}: `{}`", name, expected, arity, effect))
+    }
+}
+
+// The number of characters to insert, remove or replace to turn `lhs` into `rhs`
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs: Vec<_> = rhs.chars().collect();
+    let mut previous: Vec<_> = (0..=rhs.len()).collect();
+    for (i, l) in lhs.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, r) in rhs.iter().enumerate() {
+            let replace = previous[j] + usize::from(l != *r);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[rhs.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effect_kind_parse() {
+        let (rest, effect) = EffectKind::parse("connect(address, port ?, protocol?) => network(connect)").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(effect.name, "connect");
+        assert_eq!(effect.params, vec![
+            Param { kind: ParamKind::Address, repetition: Repetition::Once },
+            Param { kind: ParamKind::Port, repetition: Repetition::Optional },
+            Param { kind: ParamKind::Protocol, repetition: Repetition::Optional },
+        ]);
+        assert_eq!(effect.mappings, vec![SandboxMapping::Network("connect".to_string())]);
+        assert_eq!(effect.arity(), (1, Some(3)));
+        assert_eq!(effect.to_string(), "connect(address, port?, protocol?) => network(connect)");
+
+        let (_, effect) = EffectKind::parse("exec(program, value..) => exec").unwrap();
+        assert_eq!(effect.arity(), (1, None));
+        let (_, effect) = EffectKind::parse("copies_file(path, path) => file(r), file(w)").unwrap();
+        assert_eq!(effect.mappings.len(), 2);
+        let (_, effect) = EffectKind::parse("reads_env(name)").unwrap();
+        assert!(effect.mappings.is_empty());
+
+        assert!(EffectKind::parse("reads_file(file)").is_err());
+        let (rest, _) = EffectKind::parse("reads_file(path) => file(x)").unwrap();
+        assert_ne!(rest, "");
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = EffectRegistry::builtin();
+        assert!(registry.mapped_to("reads_file", &SandboxMapping::File("r".to_string())).is_some());
+        assert!(registry.mapped_to("exec", &SandboxMapping::Exec).is_some());
+        assert_eq!(registry.check("reads_file", 1), None);
+        assert_eq!(registry.check("exec", 4), None);
+        assert_eq!(registry.check("read_file", 1).unwrap(), "unknown effect `read_file`, did you mean `reads_file`?");
+        assert_eq!(registry.check("frobnicate", 1).unwrap(), "unknown effect `frobnicate`");
+        assert_eq!(
+            registry.check("reads_file", 2).unwrap(),
+            "`reads_file` takes 1 argument, found 2: `reads_file(path) => file(r)`",
+        );
+        assert_eq!(registry.check("connect", 0).unwrap().split(':').next().unwrap(), "`connect` takes 1 to 3 arguments, found 0");
+
+        let custom = EffectRegistry::parse("\
+            # Certificates are only ever read\n\
+            \n\
+            reads_cert(path) => file(r)\n\
+            reads_file(path, value?) => file(r)\n\
+        ").unwrap();
+        registry.extend(custom);
+        assert_eq!(registry.check("reads_cert", 1), None);
+        assert_eq!(registry.check("reads_file", 2), None);
+        assert_eq!(registry.effects.iter().filter(|effect| effect.name == "reads_file").count(), 1);
+
+        let error = EffectRegistry::parse("reads_file(path)\nreads_file(path) =>").unwrap_err();
+        assert!(error.starts_with("line 2: "), "{}", error);
+    }
+}
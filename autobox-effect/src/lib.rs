@@ -1,11 +1,46 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
 
+use autobox_effect_parser::ast::{DeclareMacro, EffectDefinition};
+use autobox_effect_parser::registry::EffectRegistry;
+
+/// The registry of the crate using the macro, next to its `Cargo.toml`
+const REGISTRY_FILE: &str = "autobox.effects";
+
 #[proc_macro_attribute]
-pub fn declare(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn declare(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut item = item;
+    // A declaration `autobox-cli` can't parse is reported by it, the build shouldn't break over it
+    let attr = attr.to_string();
+    let declaration = match DeclareMacro::parse(&attr) {
+        Ok((_, declaration)) => declaration,
+        Err(_) => return item,
+    };
+    let registry = load_registry();
+    let stmts = declaration.side_effects.iter().flat_map(|side_effects| side_effects.side_effect_stmts.iter());
+    for stmt in stmts {
+        if let Some(message) = registry.check(stmt.side_effect_name, stmt.side_effect_arguments.len()) {
+            item.extend(warning(&message));
+        }
+    }
     item
 }
 
+/// An effect made of other effects, ie: `effect::define!(reads_config(p: path) => reads_file(p), stat(p));`.
+/// It expands to nothing, `autobox-cli` reads the definition from the source
+#[proc_macro]
+pub fn define(input: TokenStream) -> TokenStream {
+    let input = input.to_string();
+    let message = match EffectDefinition::parse(&input) {
+        Ok(("", definition)) => match definition.unknown_variables().first() {
+            Some(variable) => format!("`{}` is not a parameter of `{}`", variable, definition.name),
+            None => return TokenStream::new(),
+        },
+        _ => "expected an effect definition like `reads_config(p: path) => reads_file(p), stat(p)`".to_string(),
+    };
+    format!("compile_error!({:?});", message).parse().unwrap()
+}
+
 #[proc_macro_attribute]
 pub fn entrypoint(_attr: TokenStream, item: TokenStream) -> TokenStream {
     item
@@ -15,3 +50,30 @@ pub fn entrypoint(_attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn infer(_attr: TokenStream, item: TokenStream) -> TokenStream {
     item
 }
+
+// The builtin effects and those of the crate's `autobox.effects`. A registry that can't be read or
+// parsed is left out, `autobox-cli` reports why
+fn load_registry() -> EffectRegistry {
+    let mut registry = EffectRegistry::builtin();
+    let custom = std::env::var("CARGO_MANIFEST_DIR")
+        .ok()
+        .and_then(|dir| std::fs::read_to_string(std::path::Path::new(&dir).join(REGISTRY_FILE)).ok())
+        .and_then(|custom| EffectRegistry::parse(&custom).ok());
+    if let Some(custom) = custom {
+        registry.extend(custom);
+    }
+    registry
+}
+
+// Stable proc macros can't emit warnings, but using a deprecated item does, with its note
+fn warning(message: &str) -> TokenStream {
+    format!(
+        "const _: () = {{ \
+            #[deprecated(note = {:?})] \
+            #[allow(non_upper_case_globals)] \
+            const autobox_effect: () = (); \
+            autobox_effect \
+        }};",
+        message,
+    ).parse().unwrap()
+}
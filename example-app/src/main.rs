@@ -7,7 +7,7 @@ use autobox_effect as effect;
     returns=(A + '/' + B),
 )]
 fn fn_with_effects(a: &str, b: &str) -> String {
-    std::fs::read_to_string(format!("{a}/{b}"));
+    let _ = std::fs::read_to_string(format!("{a}/{b}"));
     format!("{a}/{b}")
 }
 